@@ -0,0 +1,188 @@
+use crate::entry::Entry;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Archive is a file that holds one year's entries after they were moved out of the data file.
+/// Archives are kept beside the data file, e.g. t-2022.csv next to t.csv.
+#[derive(Debug, PartialEq)]
+pub struct Archive {
+    pub path: PathBuf,
+    pub year: i32,
+}
+
+/// List the archives of a data file, oldest first. Together with the data file, they're the
+/// whole history.
+pub fn list<P: AsRef<Path>>(t_data_file: P) -> io::Result<Vec<Archive>> {
+    let t_data_file = t_data_file.as_ref();
+    let (stem, ext) = split_name(t_data_file);
+    let dir = match t_data_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut archives = vec![];
+    for entry in entries {
+        let name = entry?.file_name();
+        let year = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&stem))
+            .and_then(|rest| rest.strip_prefix('-'))
+            .and_then(|rest| rest.strip_suffix(&ext))
+            .filter(|year| year.len() == 4 && year.bytes().all(|c| c.is_ascii_digit()))
+            .and_then(|year| year.parse().ok());
+        if let Some(year) = year {
+            archives.push(Archive {
+                path: t_data_file.with_file_name(name),
+                year,
+            });
+        }
+    }
+    archives.sort_by_key(|archive| archive.year);
+    Ok(archives)
+}
+
+/// Where the archive for a year goes, whether or not it exists yet.
+pub fn path<P: AsRef<Path>>(t_data_file: P, year: i32) -> PathBuf {
+    let t_data_file = t_data_file.as_ref();
+    let (stem, ext) = split_name(t_data_file);
+    t_data_file.with_file_name(format!("{stem}-{year:04}{ext}"))
+}
+
+// Split t.csv into "t" and ".csv". A leading dot, like in .t.csv, is part of the stem.
+fn split_name(t_data_file: &Path) -> (String, String) {
+    let name = t_data_file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    match name.rfind('.') {
+        Some(i) if i > 0 => (name[..i].to_string(), name[i..].to_string()),
+        _ => (name, String::new()),
+    }
+}
+
+/// Split off the entries at the start of the file that can be archived, grouped by the year they
+/// started in. That's every finished entry up to the first one that's from `before` or later, so
+/// that the archives and what's left are still in the same order as the file. A note goes with the
+/// entry before it, or with the first entry if there's nothing before it.
+pub fn split(mut entries: Vec<Entry>, before: i32) -> (Vec<(i32, Vec<Entry>)>, Vec<Entry>) {
+    let end = entries
+        .iter()
+        .position(|entry| match entry {
+            Entry::Time(te) => !te.is_finished() || te.start.datetime().year() >= before,
+            Entry::Note(_) => false,
+        })
+        .unwrap_or(entries.len());
+    let live = entries.split_off(end);
+    let mut years: Vec<(i32, Vec<Entry>)> = vec![];
+    let mut leading_notes = vec![];
+    for entry in entries {
+        match (&entry, years.last_mut()) {
+            (Entry::Time(te), Some((year, archived))) if te.start.datetime().year() == *year => {
+                archived.push(entry)
+            }
+            (Entry::Time(te), _) => {
+                let year = te.start.datetime().year();
+                let mut archived = std::mem::take(&mut leading_notes);
+                archived.push(entry);
+                years.push((year, archived));
+            }
+            (Entry::Note(_), Some((_, archived))) => archived.push(entry),
+            (Entry::Note(_), None) => leading_notes.push(entry),
+        }
+    }
+    if years.is_empty() {
+        // There are only notes, and nothing to file them with.
+        let mut entries = leading_notes;
+        entries.extend(live);
+        return (years, entries);
+    }
+    (years, live)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list, path, split};
+    use crate::entry::Entry;
+    use crate::parser::{parse_entries, write_entries};
+    use crate::timesource::mock_time::mock_time;
+    use pretty_assertions::assert_eq;
+    use std::path::Path;
+    use time::{date, offset, time};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    fn parse(s: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+        let ts = mock_time(date!(2023 - 03 - 01), time!(10:00), offset!(-05:00));
+        Ok(parse_entries(s.as_bytes(), &ts)?)
+    }
+
+    fn write(entries: &[Entry]) -> Result<String, Box<dyn std::error::Error>> {
+        let mut output = vec![];
+        write_entries(&mut output, entries)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn test_list_and_path() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let t_data_file = dir.path().join(".t.csv");
+        for name in [
+            ".t-2022.csv",
+            ".t-2021.csv",
+            ".t-22.csv",
+            "t-2020.csv",
+            ".t.csv",
+        ] {
+            std::fs::write(dir.path().join(name), "")?;
+        }
+        let years: Vec<i32> = list(&t_data_file)?.iter().map(|a| a.year).collect();
+        assert_eq!(vec![2021, 2022], years);
+        assert_eq!(dir.path().join(".t-2019.csv"), path(&t_data_file, 2019));
+        assert_eq!(Path::new("data-2019"), path("data", 2019));
+        Ok(())
+    }
+
+    #[test]
+    fn test_split() -> TestRes {
+        let entries = parse(
+            "# before anything\n\
+             2021-12-31 22:00 -0500,2022-01-01 01:00 -0500\n\
+             # late\n\
+             2022-03-01 09:00 -0500,2022-03-01 10:00 -0500\n\
+             2023-01-02 09:00 -0500,2023-01-02 10:00 -0500\n\
+             2022-12-30 09:00 -0500,2022-12-30 10:00 -0500\n",
+        )?;
+        let (years, live) = split(entries, 2023);
+        assert_eq!(
+            vec![2021, 2022],
+            years.iter().map(|y| y.0).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "# before anything\n\
+             2021-12-31 22:00 -0500,2022-01-01 01:00 -0500\n\
+             # late\n",
+            write(&years[0].1)?
+        );
+        // The entry from 2022 after the one from 2023 stays, to keep the order.
+        assert_eq!(2, live.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_unfinished() -> TestRes {
+        let entries = parse(
+            "2021-03-01 09:00 -0500\n\
+             2021-03-02 09:00 -0500,2021-03-02 10:00 -0500\n",
+        )?;
+        let (years, live) = split(entries, 2023);
+        assert!(years.is_empty());
+        assert_eq!(2, live.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+/// How many backups are kept if config.toml doesn't say.
+pub const DEFAULT_BACKUPS: usize = 10;
+
+const SUFFIX_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Backup is a copy of the data file from just before t changed it. Backups are kept beside the
+/// data file, e.g. t.csv.backup-20200807T140000Z.
+#[derive(Debug, PartialEq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken: OffsetDateTime,
+}
+
+/// List the backups of a data file, newest first.
+pub fn list<P: AsRef<Path>>(t_data_file: P) -> io::Result<Vec<Backup>> {
+    let t_data_file = t_data_file.as_ref();
+    let prefix = backup_prefix(t_data_file);
+    let dir = match t_data_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut backups = vec![];
+    for entry in entries {
+        let name = entry?.file_name();
+        let taken = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|suffix| PrimitiveDateTime::parse(suffix, SUFFIX_FORMAT).ok());
+        if let Some(taken) = taken {
+            backups.push(Backup {
+                path: t_data_file.with_file_name(name),
+                taken: taken.assume_utc(),
+            });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.taken));
+    Ok(backups)
+}
+
+/// Save the data file as it is now, and remove all but the newest `keep` backups. This is called
+/// right before the data file is replaced. It does nothing if `keep` is 0 or there's nothing in
+/// the data file yet.
+pub fn back_up<P: AsRef<Path>>(t_data_file: P, keep: usize, now: OffsetDateTime) -> io::Result<()> {
+    let t_data_file = t_data_file.as_ref();
+    let is_empty = fs::metadata(t_data_file).map_or(true, |metadata| metadata.len() == 0);
+    if keep == 0 || is_empty {
+        return Ok(());
+    }
+    let name = format!(
+        "{}{}",
+        backup_prefix(t_data_file),
+        now.to_offset(UtcOffset::UTC).format(SUFFIX_FORMAT)
+    );
+    let path = t_data_file.with_file_name(name);
+    // If there's already a backup from this second, it's older, so it's the one to keep. A hard
+    // link would be cheaper than a copy, but an editor that writes in place would change the
+    // backup too.
+    if !path.exists() {
+        fs::copy(t_data_file, &path)?;
+    }
+    for old in list(t_data_file)?.into_iter().skip(keep) {
+        fs::remove_file(old.path)?;
+    }
+    Ok(())
+}
+
+fn backup_prefix(t_data_file: &Path) -> String {
+    let name = t_data_file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    format!("{}.backup-", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{back_up, list};
+    use pretty_assertions::assert_eq;
+    use time::{date, offset, time, Duration};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_back_up_and_rotate() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("t.csv");
+        let start = date!(2020 - 08 - 07)
+            .with_time(time!(10:00))
+            .assume_offset(offset!(-04:00));
+
+        // Nothing to back up yet.
+        back_up(&path, 3, start)?;
+        assert_eq!(0, list(&path)?.len());
+
+        for i in 0..5 {
+            std::fs::write(&path, format!("version {}\n", i))?;
+            back_up(&path, 3, start + Duration::minutes(i))?;
+        }
+        let backups = list(&path)?;
+        assert_eq!(3, backups.len());
+        assert_eq!(start + Duration::minutes(4), backups[0].taken);
+        assert_eq!(
+            "t.csv.backup-20200807T140300Z",
+            backups[1].path.file_name().unwrap().to_string_lossy()
+        );
+        assert_eq!("version 4\n", std::fs::read_to_string(&backups[0].path)?);
+        assert_eq!("version 2\n", std::fs::read_to_string(&backups[2].path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_backups() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("t.csv");
+        std::fs::write(&path, "")?;
+        back_up(&path, 3, time::OffsetDateTime::now_utc())?;
+        assert_eq!(0, list(&path)?.len());
+        std::fs::write(&path, "# a note\n")?;
+        back_up(&path, 0, time::OffsetDateTime::now_utc())?;
+        assert_eq!(0, list(&path)?.len());
+        assert_eq!(0, list(dir.path().join("missing").join("t.csv"))?.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,779 @@
+use crate::archive::{self, Archive};
+use crate::calendar::Calendar;
+use crate::entry::{Entry, TimeEntry};
+use crate::lock::lock_shared;
+use crate::parser::{entries, ParseError};
+use crate::timesource::TimeSource;
+use crate::totals::{append_weeks, each_week_totals, WeekTotals};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::{File, Metadata};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use time::Date;
+
+const HEADER: &str = "t cache 4";
+
+/// How much of the end of the data file is hashed, to catch a change that keeps its size and, on a
+/// file system with coarse times, its modification time.
+const HASHED_BLOCK: usize = 4096;
+
+/// Totals has the totals for every week in a data file, plus the syntax errors in the part of the
+/// file that had to be parsed.
+#[derive(Debug, Default)]
+pub struct Totals {
+    pub weeks: Vec<WeekTotals>,
+    pub errors: Vec<ParseError>,
+}
+
+/// Add up the time in each week of a data file and its archives. The weeks that can't change any
+/// more unless the file is edited by hand are kept in a cache beside it, e.g. t.csv.cache, so
+/// usually only the last week or so of entries has to be parsed. When t changes the data file,
+/// it keeps the cache in step with `track_write`; any other change means adding it all up again.
+pub fn weekly_totals<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    cal: &Calendar,
+    ts: &TS,
+) -> Result<Totals, Box<dyn Error>> {
+    let t_data_file = t_data_file.as_ref();
+    let _lock = lock_shared(t_data_file)?;
+    let archives = archive::list(t_data_file)?;
+    let mut f = match File::open(t_data_file) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return uncached(&archives, cal, ts),
+        Err(e) => return Err(e.into()),
+    };
+    let metadata = f.metadata()?;
+    let settings = settings(cal, ts);
+    let signature = signature(&archives)?;
+    let old = match Cache::load(t_data_file) {
+        Ok(Some(cache))
+            if cache.settings == settings
+                && cache.archives == signature
+                && cache.is_valid_for(&metadata, hash_last_block(&mut f, metadata.len())?) =>
+        {
+            cache
+        }
+        // A cache that can't be read is rebuilt, just like one that's out of date.
+        _ => Cache::empty(settings.clone(), signature.clone()),
+    };
+    let old_stamp = (old.pos, old.len, old.mtime, old.hash);
+    let (totals, cache) = match count(&mut f, &metadata, &archives, old, cal, ts)? {
+        Some(counted) => counted,
+        None => count(
+            &mut f,
+            &metadata,
+            &archives,
+            Cache::empty(settings, signature),
+            cal,
+            ts,
+        )?
+        .expect("an empty cache fits any file"),
+    };
+    if (cache.pos, cache.len, cache.mtime, cache.hash) != old_stamp {
+        // The cache is only there to save time, so the totals are still good if it can't be
+        // written, e.g. because the data file is in a read-only directory.
+        let _ = cache.save(t_data_file);
+    }
+    Ok(totals)
+}
+
+/// Keep the cache in step with a change that t just made to the data file, while it still has
+/// the exclusive lock. `before` is the data file's metadata from before the change, and `old` and
+/// `new` are its contents before and after. If the cache was good for the old file and nothing
+/// that it counted changed, it's good for the new file too. Otherwise it's removed, so that it's
+/// rebuilt.
+pub fn track_write(
+    t_data_file: &Path,
+    before: Option<&Metadata>,
+    old: &[u8],
+    new: &[u8],
+) -> io::Result<()> {
+    let changed_at = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count() as u64;
+    let still_good = match Cache::load(t_data_file) {
+        Ok(None) => return Ok(()),
+        Ok(Some(cache)) => Some(cache).filter(|cache| {
+            before.is_some_and(|before| cache.is_valid_for(before, hash_end(old)))
+                && cache.pos <= changed_at
+        }),
+        Err(_) => None,
+    };
+    match still_good {
+        Some(mut cache) => {
+            let metadata = std::fs::metadata(t_data_file)?;
+            cache.len = metadata.len();
+            cache.mtime = mtime(&metadata);
+            cache.hash = hash_end(new);
+            cache.save(t_data_file)
+        }
+        None => match std::fs::remove_file(cache_path(t_data_file)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            res => res,
+        },
+    }
+}
+
+/// Cache has the totals of the weeks at the start of a data file.
+#[derive(Debug, PartialEq)]
+struct Cache {
+    /// The calendar and time zone that the totals were added up with.
+    settings: String,
+    /// The year, size and modification time of each archive. The archives are counted along with
+    /// the start of the data file, so the cache is rebuilt if they change.
+    archives: String,
+    /// The data file's size, modification time and a hash of its last block when the cache was
+    /// written. The time is in nanoseconds since the epoch.
+    len: u64,
+    mtime: u128,
+    hash: u64,
+    /// Every entry before this position in the data file is counted in `weeks`.
+    pos: u64,
+    /// The first week that isn't in `weeks`. The entries after `pos` all run into it or a later
+    /// week, and only their totals from this week on are added.
+    next_week: Option<Date>,
+    weeks: Vec<WeekTotals>,
+}
+
+impl Cache {
+    fn empty(settings: String, archives: String) -> Self {
+        Self {
+            settings,
+            archives,
+            len: 0,
+            mtime: 0,
+            hash: hash_end(&[]),
+            pos: 0,
+            next_week: None,
+            weeks: vec![],
+        }
+    }
+
+    /// The cache is only good for the file it was written for, or for the file that t changed it
+    /// into with `track_write`. `hash` is the hash of the file's last block.
+    fn is_valid_for(&self, metadata: &Metadata, hash: u64) -> bool {
+        self.len == metadata.len() && self.mtime == mtime(metadata) && self.hash == hash
+    }
+
+    fn load(t_data_file: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        match File::open(cache_path(t_data_file)) {
+            Ok(f) => Ok(Some(read_cache(BufReader::new(f))?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Readers share the lock on the data file, so each one writes its own temporary file.
+    fn save(&self, t_data_file: &Path) -> io::Result<()> {
+        let path = cache_path(t_data_file);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}", std::process::id()));
+        let mut f = File::create(&tmp)?;
+        self.write(&mut f)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "{HEADER}")?;
+        writeln!(w, "settings {}", self.settings)?;
+        writeln!(w, "archives {}", self.archives)?;
+        writeln!(w, "file {} {} {}", self.len, self.mtime, self.hash)?;
+        let next_week = self
+            .next_week
+            .map_or("-".to_string(), |week| week.format("%Y-%m-%d"));
+        writeln!(w, "counted {} {}", self.pos, next_week)?;
+        for week in &self.weeks {
+            let days: Vec<String> = week.days.iter().map(i64::to_string).collect();
+            let segments: Vec<String> = week
+                .segments
+                .iter()
+                .map(|day| {
+                    let day: Vec<String> = day.iter().map(i64::to_string).collect();
+                    day.join(",")
+                })
+                .collect();
+            writeln!(
+                w,
+                "week {} {} {} {} {} {}",
+                week.start.format("%Y-%m-%d"),
+                week.entries,
+                week.pauses,
+                week.minutes,
+                days.join(","),
+                if segments.is_empty() {
+                    "-".to_string()
+                } else {
+                    segments.join("/")
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn read_cache(r: impl BufRead) -> Result<Cache, Box<dyn Error>> {
+    let mut lines = r.lines();
+    if lines.next().transpose()?.as_deref() != Some(HEADER) {
+        return Err(corrupt("unrecognized header"));
+    }
+    let mut next = |name: &str| -> Result<String, Box<dyn Error>> {
+        let line = lines.next().transpose()?.unwrap_or_default();
+        match line
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(' '))
+        {
+            Some(rest) => Ok(rest.to_string()),
+            None => Err(corrupt(&format!("expected {name}"))),
+        }
+    };
+    let settings = next("settings")?;
+    let archives = next("archives")?;
+    let (len, mtime, hash) = match next("file")?.split(' ').collect::<Vec<_>>().as_slice() {
+        [len, mtime, hash] => (number(len)?, number(mtime)?, number(hash)?),
+        _ => return Err(corrupt("expected a size, a time and a hash")),
+    };
+    let counted = next("counted")?;
+    let (pos, next_week) = match counted.split(' ').collect::<Vec<_>>().as_slice() {
+        [pos, "-"] => (number(pos)?, None),
+        [pos, week] => (number(pos)?, Some(date(week)?)),
+        _ => return Err(corrupt("expected a position and a week")),
+    };
+    let mut weeks = vec![];
+    for line in lines {
+        match line?.strip_prefix("week ") {
+            Some(week) => weeks.push(read_week(week)?),
+            None => return Err(corrupt("expected week")),
+        }
+    }
+    Ok(Cache {
+        settings,
+        archives,
+        len,
+        mtime,
+        hash,
+        pos,
+        next_week,
+        weeks,
+    })
+}
+
+fn read_week(s: &str) -> Result<WeekTotals, Box<dyn Error>> {
+    let fields: Vec<&str> = s.split(' ').collect();
+    let (start, entries, pauses, minutes, days, segments) = match fields.as_slice() {
+        [start, entries, pauses, minutes, days, segments] => {
+            (*start, *entries, *pauses, *minutes, *days, *segments)
+        }
+        _ => return Err(corrupt("expected a week")),
+    };
+    let mut week = WeekTotals::empty(date(start)?);
+    week.entries = number(entries)?;
+    week.pauses = number(pauses)?;
+    week.minutes = number(minutes)?;
+    let days: Vec<i64> = days.split(',').map(number).collect::<Result<_, _>>()?;
+    week.days = days.try_into().map_err(|_| corrupt("expected 7 days"))?;
+    if segments != "-" {
+        for day in segments.split('/') {
+            week.segments
+                .push(day.split(',').map(number).collect::<Result<_, _>>()?);
+        }
+    }
+    Ok(week)
+}
+
+fn date(s: &str) -> Result<Date, Box<dyn Error>> {
+    Date::parse(s, "%Y-%m-%d").map_err(|_| corrupt("bad date"))
+}
+
+fn number<T: std::str::FromStr>(s: &str) -> Result<T, Box<dyn Error>> {
+    s.parse().map_err(|_| corrupt("bad number"))
+}
+
+fn corrupt(message: &str) -> Box<dyn Error> {
+    format!("the totals cache is corrupt: {message}").into()
+}
+
+/// Parse the entries after the cache, and count the ones that are done. Returns None if the
+/// entries don't fit after the ones that are already in the cache.
+fn count<TS: TimeSource>(
+    f: &mut File,
+    metadata: &Metadata,
+    archives: &[Archive],
+    mut cache: Cache,
+    cal: &Calendar,
+    ts: &TS,
+) -> Result<Option<(Totals, Cache)>, Box<dyn Error>> {
+    // Each entry comes with where it starts in the data file, or None if it's in an archive.
+    let mut parsed = vec![];
+    let mut errors = vec![];
+    if cache.next_week.is_none() {
+        for archive in archives {
+            parse_from(File::open(&archive.path)?, 0, &mut parsed, &mut errors, ts)?;
+        }
+        for entry in &mut parsed {
+            entry.0 = None;
+        }
+    }
+    f.seek(SeekFrom::Start(cache.pos))?;
+    parse_from(&mut *f, cache.pos, &mut parsed, &mut errors, ts)?;
+
+    let in_order = parsed
+        .windows(2)
+        .all(|pair| pair[0].1.start.datetime() <= pair[1].1.start.datetime());
+    let after_cache = match cache.next_week {
+        Some(next_week) => parsed.iter().all(|(_, te)| runs_into(te, next_week, cal)),
+        None => true,
+    };
+    if !after_cache || (!in_order && cache.pos > 0) {
+        return Ok(None);
+    }
+
+    // Entries that are out of order get grouped into weeks differently depending on what's
+    // before them, and entries with syntax errors might be fixed later, so nothing more is
+    // cached until they're sorted out.
+    if in_order && errors.is_empty() {
+        // Only a position in the data file can be cached, so an entry from an archive that runs
+        // into the cut week has to wait for a later cut.
+        let cut = find_cut(&parsed, cal)
+            .and_then(|(live, cut, cut_week)| parsed[live].0.map(|pos| (live, pos, cut, cut_week)));
+        if let Some((live, pos, cut, cut_week)) = cut {
+            let counted = parsed[..cut].iter().map(|(_, te)| te.clone());
+            append_weeks(
+                &mut cache.weeks,
+                weeks_between(counted, cache.next_week, Some(cut_week), cal, ts),
+            );
+            cache.pos = pos;
+            cache.next_week = Some(cut_week);
+            parsed.drain(..live);
+        }
+    }
+    cache.len = metadata.len();
+    cache.mtime = mtime(metadata);
+    cache.hash = hash_last_block(f, metadata.len())?;
+
+    let mut weeks = cache.weeks.clone();
+    let live = parsed.into_iter().map(|(_, te)| te);
+    append_weeks(
+        &mut weeks,
+        weeks_between(live, cache.next_week, None, cal, ts),
+    );
+    Ok(Some((Totals { weeks, errors }, cache)))
+}
+
+fn parse_from<R: Read, TS: TimeSource>(
+    r: R,
+    pos: u64,
+    parsed: &mut Vec<(Option<u64>, TimeEntry)>,
+    errors: &mut Vec<ParseError>,
+    ts: &TS,
+) -> io::Result<()> {
+    let mut iter = entries(BufReader::new(r), ts);
+    while let Some(entry) = iter.next() {
+        match entry {
+            Ok(Entry::Time(te)) => parsed.push((Some(pos + iter.entry_start()), te)),
+            Ok(Entry::Note(_)) => (),
+            Err(ParseError::Io(e)) => return Err(e),
+            Err(e) => errors.push(e),
+        }
+    }
+    Ok(())
+}
+
+// Without a data file, there's nowhere to say how much of it was counted.
+fn uncached<TS: TimeSource>(
+    archives: &[Archive],
+    cal: &Calendar,
+    ts: &TS,
+) -> Result<Totals, Box<dyn Error>> {
+    let mut parsed = vec![];
+    let mut errors = vec![];
+    for archive in archives {
+        parse_from(File::open(&archive.path)?, 0, &mut parsed, &mut errors, ts)?;
+    }
+    let entries = parsed.into_iter().map(|(_, te)| te);
+    Ok(Totals {
+        weeks: each_week_totals(entries, cal, ts).collect(),
+        errors,
+    })
+}
+
+/// The totals of the entries' weeks from `from` until just before `until`.
+fn weeks_between<I: IntoIterator<Item = TimeEntry>, TS: TimeSource>(
+    entries: I,
+    from: Option<Date>,
+    until: Option<Date>,
+    cal: &Calendar,
+    ts: &TS,
+) -> Vec<WeekTotals> {
+    each_week_totals(entries, cal, ts)
+        .filter(|week| from.is_none_or(|from| week.start >= from))
+        .filter(|week| until.is_none_or(|until| week.start < until))
+        .collect()
+}
+
+/// Whether an entry runs into a week or past it. An entry that hasn't been stopped could run into
+/// any week.
+fn runs_into(te: &TimeEntry, week: Date, cal: &Calendar) -> bool {
+    match &te.stop {
+        Some(stop) => cal.start_of_week(cal.workday(stop.datetime())) >= week,
+        None => true,
+    }
+}
+
+/// Find the first week that can't be counted yet, and the first entry in it. The last week's
+/// entries can still be stopped or added to, and so can an entry that hasn't been stopped. Also
+/// returns the first entry that runs into that week, which has to be parsed again along with the
+/// entries after it. Returns None if nothing can be counted.
+fn find_cut(parsed: &[(Option<u64>, TimeEntry)], cal: &Calendar) -> Option<(usize, usize, Date)> {
+    let week_of = |te: &TimeEntry| cal.start_of_week(cal.workday(te.start.datetime()));
+    let last_week = week_of(&parsed.last()?.1);
+    let cut = parsed
+        .iter()
+        .position(|(_, te)| !te.is_finished() || week_of(te) >= last_week)?;
+    if cut == 0 {
+        return None;
+    }
+    let cut_week = week_of(&parsed[cut].1);
+    let live = parsed[..cut]
+        .iter()
+        .position(|(_, te)| runs_into(te, cut_week, cal))
+        .unwrap_or(cut);
+    Some((live, cut, cut_week))
+}
+
+fn signature(archives: &[Archive]) -> io::Result<String> {
+    if archives.is_empty() {
+        return Ok("-".to_string());
+    }
+    let mut files = vec![];
+    for archive in archives {
+        let metadata = std::fs::metadata(&archive.path)?;
+        files.push(format!(
+            "{}:{}:{}",
+            archive.year,
+            metadata.len(),
+            mtime(&metadata)
+        ));
+    }
+    Ok(files.join(","))
+}
+
+fn settings<TS: TimeSource>(cal: &Calendar, ts: &TS) -> String {
+    format!(
+        "{:?} {} {}",
+        cal.week_start,
+        cal.day_rollover.format("%H:%M"),
+        ts.zone()
+    )
+}
+
+fn mtime(metadata: &Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos())
+}
+
+fn hash_last_block(f: &mut File, len: u64) -> io::Result<u64> {
+    f.seek(SeekFrom::Start(len.saturating_sub(HASHED_BLOCK as u64)))?;
+    let mut block = vec![];
+    (&mut *f)
+        .take(HASHED_BLOCK as u64)
+        .read_to_end(&mut block)?;
+    Ok(hash_end(&block))
+}
+
+fn hash_end(contents: &[u8]) -> u64 {
+    let block = &contents[contents.len().saturating_sub(HASHED_BLOCK)..];
+    // FNV-1a
+    block.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn cache_path(t_data_file: &Path) -> PathBuf {
+    let mut name = t_data_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".cache");
+    t_data_file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_path, read_cache, weekly_totals, Cache};
+    use crate::calendar::Calendar;
+    use crate::file::replace_data_file;
+    use crate::parser::parse_time_entries;
+    use crate::timesource::mock_time::{mock_time, MockTimeSource};
+    use crate::totals::{each_week_totals, WeekTotals};
+    use pretty_assertions::assert_eq;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::{Path, PathBuf};
+    use time::{date, offset, time, Weekday};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    fn fixture(dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let mut source = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?);
+        source.push("tests/fixtures/file/thousand-entries.csv");
+        let path = dir.join("t.csv");
+        std::fs::copy(source, &path)?;
+        Ok(path)
+    }
+
+    fn ts() -> MockTimeSource {
+        mock_time(date!(2020 - 08 - 14), time!(12:00), offset!(-04:00))
+    }
+
+    fn without_cache(
+        path: &Path,
+        cal: &Calendar,
+    ) -> Result<Vec<WeekTotals>, Box<dyn std::error::Error>> {
+        let entries = parse_time_entries(File::open(path)?, &ts())?;
+        Ok(each_week_totals(entries, cal, &ts()).collect())
+    }
+
+    fn load(path: &Path) -> Result<Cache, Box<dyn std::error::Error>> {
+        read_cache(BufReader::new(File::open(cache_path(path))?))
+    }
+
+    // Add to the data file the way t does, which keeps the cache up to date.
+    fn append(path: &Path, s: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut contents = std::fs::read(path)?;
+        contents.extend_from_slice(s.as_bytes());
+        replace_data_file(path, &contents)
+    }
+
+    #[test]
+    fn test_cache_matches_parsing() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        let cal = Calendar::default();
+        let check = || -> TestRes {
+            assert_eq!(
+                without_cache(&path, &cal)?,
+                weekly_totals(&path, &cal, &ts())?.weeks
+            );
+            Ok(())
+        };
+
+        check()?;
+        let cache = load(&path)?;
+        // Everything but the last week is cached.
+        assert_eq!(date!(2020 - 07 - 26), cache.weeks.last().unwrap().start);
+        check()?;
+
+        // Start, stop and then add entries the way t does.
+        append(&path, "2020-08-09 09:00 -0400\n")?;
+        check()?;
+        let contents = std::fs::read_to_string(&path)?;
+        replace_data_file(
+            &path,
+            contents
+                .replace(
+                    "2020-08-09 09:00 -0400\n",
+                    "2020-08-09 09:00 -0400,2020-08-09 10:00 -0400\n  # a note\n",
+                )
+                .as_bytes(),
+        )?;
+        check()?;
+        assert_eq!(
+            date!(2020 - 08 - 02),
+            load(&path)?.weeks.last().unwrap().start
+        );
+        append(
+            &path,
+            "# a free note\n\n2020-08-13 23:00 -0400,2020-08-14 01:00 -0400\n",
+        )?;
+        check()?;
+
+        // The weeks that were cached are gone.
+        std::fs::write(&path, "2020-08-13 09:00 -0400,2020-08-13 10:00 -0400\n")?;
+        check()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_is_used() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        let cal = Calendar::default();
+        weekly_totals(&path, &cal, &ts())?;
+
+        let mut cache = load(&path)?;
+        cache.weeks[0].minutes = 12345;
+        cache.save(&path)?;
+        append(&path, "2020-08-10 09:00 -0400,2020-08-10 10:00 -0400\n")?;
+        assert_eq!(12345, weekly_totals(&path, &cal, &ts())?.weeks[0].minutes);
+
+        // The end of what's cached changed, so it's all added up again.
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::write(&path, contents.replace("2020-07-3", "2020-07-2"))?;
+        let weeks = weekly_totals(&path, &cal, &ts())?.weeks;
+        assert_eq!(without_cache(&path, &cal)?, weeks);
+        assert!(weeks[0].minutes != 12345);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edits_before_the_end() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        let cal = Calendar::default();
+        let check = || -> TestRes {
+            assert_eq!(
+                without_cache(&path, &cal)?,
+                weekly_totals(&path, &cal, &ts())?.weeks
+            );
+            Ok(())
+        };
+        check()?;
+
+        // An edit by hand early on that doesn't change the size of the file.
+        let contents = std::fs::read_to_string(&path)?;
+        let edited = contents.replacen("2019-06-25 07:30", "2019-06-25 06:30", 1);
+        std::fs::write(&path, &edited)?;
+        check()?;
+
+        // The same kind of change by t.
+        replace_data_file(&path, contents.as_bytes())?;
+        check()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_that_keeps_size_and_time() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        let cal = Calendar::default();
+        weekly_totals(&path, &cal, &ts())?;
+
+        // Like an edit on a file system whose times are too coarse to tell that it changed.
+        let modified = std::fs::metadata(&path)?.modified()?;
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::write(
+            &path,
+            contents.replacen("2020-07-27 07:44", "2020-07-27 06:44", 1),
+        )?;
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(modified)?;
+        assert_eq!(
+            without_cache(&path, &cal)?,
+            weekly_totals(&path, &cal, &ts())?.weeks
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_across_weeks() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("t.csv");
+        // Every Saturday's entry runs into Sunday, which starts a new week.
+        let mut contents = String::new();
+        for day in 0..6 {
+            let saturday = date!(2020 - 06 - 27) + time::Duration::weeks(day);
+            let sunday = saturday.next_day();
+            contents += &format!(
+                "{} 22:00 -0400,{} 01:00 -0400\n{} 09:00 -0400,{} 10:00 -0400\n",
+                saturday, sunday, sunday, sunday
+            );
+        }
+        std::fs::write(&path, contents)?;
+        let cal = Calendar::default();
+        let check = || -> TestRes {
+            assert_eq!(
+                without_cache(&path, &cal)?,
+                weekly_totals(&path, &cal, &ts())?.weeks
+            );
+            Ok(())
+        };
+
+        check()?;
+        let cache = load(&path)?;
+        assert_eq!(Some(date!(2020 - 08 - 02)), cache.next_week);
+        assert_eq!(date!(2020 - 07 - 26), cache.weeks.last().unwrap().start);
+        check()?;
+        append(&path, "2020-08-08 22:00 -0400,2020-08-09 02:00 -0400\n")?;
+        check()?;
+        append(&path, "2020-08-10 09:00 -0400\n")?;
+        check()?;
+        assert_eq!(Some(date!(2020 - 08 - 09)), load(&path)?.next_week);
+        Ok(())
+    }
+
+    #[test]
+    fn test_archives() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        let cal = Calendar::default();
+        let expected = without_cache(&path, &cal)?;
+        weekly_totals(&path, &cal, &ts())?;
+
+        // Move 2019 into an archive, the way t archive does.
+        let contents = std::fs::read_to_string(&path)?;
+        let (old, new): (Vec<&str>, Vec<&str>) = contents
+            .split_inclusive('\n')
+            .partition(|line| line.starts_with("2019"));
+        std::fs::write(dir.path().join("t-2019.csv"), old.concat())?;
+        std::fs::write(&path, new.concat())?;
+        assert_eq!(expected, weekly_totals(&path, &cal, &ts())?.weeks);
+        assert!(load(&path)?.archives.starts_with("2019:"));
+        assert_eq!(expected, weekly_totals(&path, &cal, &ts())?.weeks);
+
+        std::fs::remove_file(&path)?;
+        // Without the data file, the weeks come from the archive, but they aren't cached.
+        let weeks = weekly_totals(&path, &cal, &ts())?.weeks;
+        assert_eq!(date!(2019 - 12 - 29), weeks.last().unwrap().start);
+        let done = weeks.len() - 1;
+        assert_eq!(expected[..done].to_vec(), weeks[..done].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_settings() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        weekly_totals(&path, &Calendar::default(), &ts())?;
+        let cal = Calendar {
+            week_start: Weekday::Monday,
+            day_rollover: time!(04:00),
+        };
+        assert_eq!(
+            without_cache(&path, &cal)?,
+            weekly_totals(&path, &cal, &ts())?.weeks
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_order_and_errors() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        let cal = Calendar::default();
+        weekly_totals(&path, &cal, &ts())?;
+        append(
+            &path,
+            "2020-08-10 09:00 -0400,2020-08-10 10:00 -0400\n\
+             2020-07-01 09:00 -0400,2020-07-01 10:00 -0400\n",
+        )?;
+        assert_eq!(
+            without_cache(&path, &cal)?,
+            weekly_totals(&path, &cal, &ts())?.weeks
+        );
+
+        let dir = tempfile::tempdir()?;
+        let path = fixture(dir.path())?;
+        append(&path, "2020-08-1x 09:00\n2020-08-12 09:00 -0400\n")?;
+        let totals = weekly_totals(&path, &cal, &ts())?;
+        assert_eq!(1, totals.errors.len());
+        // Nothing is cached until the error is fixed.
+        assert_eq!(0, load(&path)?.weeks.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use crate::zone::Zone;
+use time::{Date, Duration, OffsetDateTime, Time, Weekday};
+
+/// Calendar says where one workday ends and the next one begins, and which day starts a week.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calendar {
+    /// Time worked before this time of day counts toward the previous day.
+    pub day_rollover: Time,
+    pub week_start: Weekday,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Self {
+            day_rollover: Time::midnight(),
+            week_start: Weekday::Sunday,
+        }
+    }
+}
+
+impl Calendar {
+    /// Override settings from the environment. T_DAY_ROLLOVER is a time of day like "04:00" and
+    /// T_WEEK_START is the name of a day like "monday".
+    pub fn with_env(mut self) -> Result<Self, String> {
+        if let Ok(s) = std::env::var("T_DAY_ROLLOVER") {
+            self.day_rollover =
+                parse_time_of_day(&s).map_err(|e| format!("T_DAY_ROLLOVER: {}", e))?;
+        }
+        if let Ok(s) = std::env::var("T_WEEK_START") {
+            self.week_start = parse_weekday(&s).map_err(|e| format!("T_WEEK_START: {}", e))?;
+        }
+        Ok(self)
+    }
+
+    /// The first day of the week that a workday is in.
+    pub fn start_of_week(&self, mut date: Date) -> Date {
+        while date.weekday() != self.week_start {
+            date = date.previous_day();
+        }
+        date
+    }
+
+    /// The workday that a moment belongs to.
+    pub fn workday(&self, dt: OffsetDateTime) -> Date {
+        (dt - self.rollover_since_midnight()).date()
+    }
+
+    /// The moment that a workday starts. The offset is the zone's on that day, so that a day
+    /// when the clocks change is an hour longer or shorter than the others.
+    pub fn start_of_day(&self, date: Date, zone: &Zone) -> OffsetDateTime {
+        zone.assume(date.with_time(self.day_rollover))
+    }
+
+    fn rollover_since_midnight(&self) -> Duration {
+        self.day_rollover - Time::midnight()
+    }
+}
+
+/// Parse a time of day like "04:00".
+pub fn parse_time_of_day(s: &str) -> Result<Time, String> {
+    Time::parse(s.trim(), "%H:%M").map_err(|e| format!("{:?}: {}", s, e))
+}
+
+/// Parse the name of a day of the week, like "monday" or "Mon".
+pub fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    use Weekday::*;
+    let days = [
+        Sunday, Monday, Tuesday, Wednesday, Thursday, Friday, Saturday,
+    ];
+    let name = s.trim().to_lowercase();
+    days.iter()
+        .find(|day| {
+            let day = format!("{}", day).to_lowercase();
+            name.len() >= 3 && day.starts_with(&name)
+        })
+        .copied()
+        .ok_or_else(|| format!("{:?} is not a day of the week", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_weekday, Calendar};
+    use time::{date, offset, time, Weekday};
+
+    #[test]
+    fn test_midnight() {
+        let cal = Calendar::default();
+        let dt = date!(2020 - 08 - 07).with_time(time!(01:30));
+        assert_eq!(
+            date!(2020 - 08 - 07),
+            cal.workday(dt.assume_offset(offset!(-04:00)))
+        );
+        assert_eq!(
+            date!(2020 - 08 - 07)
+                .midnight()
+                .assume_offset(offset!(-04:00)),
+            cal.start_of_day(date!(2020 - 08 - 07), &offset!(-04:00).into())
+        );
+    }
+
+    #[test]
+    fn test_rollover() {
+        let cal = Calendar {
+            day_rollover: time!(04:00),
+            ..Calendar::default()
+        };
+        let on = |t| {
+            cal.workday(
+                date!(2020 - 08 - 07)
+                    .with_time(t)
+                    .assume_offset(offset!(-04:00)),
+            )
+        };
+        assert_eq!(date!(2020 - 08 - 06), on(time!(00:00)));
+        assert_eq!(date!(2020 - 08 - 06), on(time!(03:59)));
+        assert_eq!(date!(2020 - 08 - 07), on(time!(04:00)));
+        assert_eq!(date!(2020 - 08 - 07), on(time!(23:59)));
+        assert_eq!(
+            date!(2020 - 08 - 07)
+                .with_time(time!(04:00))
+                .assume_offset(offset!(-04:00)),
+            cal.start_of_day(date!(2020 - 08 - 07), &offset!(-04:00).into())
+        );
+    }
+
+    #[test]
+    fn test_start_of_week() {
+        let cal = Calendar::default();
+        assert_eq!(
+            date!(2020 - 08 - 02),
+            cal.start_of_week(date!(2020 - 08 - 02))
+        );
+        assert_eq!(
+            date!(2020 - 08 - 02),
+            cal.start_of_week(date!(2020 - 08 - 08))
+        );
+        let cal = Calendar {
+            week_start: Weekday::Monday,
+            ..cal
+        };
+        assert_eq!(
+            date!(2020 - 07 - 27),
+            cal.start_of_week(date!(2020 - 08 - 02))
+        );
+        assert_eq!(
+            date!(2020 - 08 - 03),
+            cal.start_of_week(date!(2020 - 08 - 03))
+        );
+        assert_eq!(
+            date!(2020 - 08 - 03),
+            cal.start_of_week(date!(2020 - 08 - 08))
+        );
+    }
+
+    #[test]
+    fn test_parse_weekday() {
+        assert_eq!(Ok(Weekday::Monday), parse_weekday("monday"));
+        assert_eq!(Ok(Weekday::Monday), parse_weekday("Mon"));
+        assert_eq!(Ok(Weekday::Thursday), parse_weekday("THURS"));
+        assert_eq!(Ok(Weekday::Sunday), parse_weekday("sunday"));
+        assert!(parse_weekday("m").is_err());
+        assert!(parse_weekday("someday").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::calendar::{parse_time_of_day, parse_weekday, Calendar};
+use serde::Deserialize;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Config holds the settings from config.toml. Every setting is optional.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where the time entries are stored. T_DATA_FILE takes precedence over this.
+    pub data_file: Option<PathBuf>,
+    /// Minutes per week that I'm aiming for.
+    pub weekly_goal: Option<i64>,
+    /// Minutes in a full work week, for the pto report.
+    pub full_week: Option<i64>,
+    /// Characters for the spark graphs, from smallest to largest.
+    pub sparks: Option<String>,
+    pub week_start: Option<String>,
+    /// The IANA time zone, like America/New_York, for times without a UTC offset. TZ takes
+    /// precedence over this.
+    pub time_zone: Option<String>,
+    pub day_rollover: Option<String>,
+    pub day_legend: Option<String>,
+    pub week_legend: Option<String>,
+    /// How many copies of the data file to keep from before it was changed. 0 turns backups off.
+    pub backups: Option<usize>,
+    /// The data file from the command line, which takes precedence over everything else.
+    #[serde(skip)]
+    pub data_file_arg: Option<PathBuf>,
+}
+
+static CURRENT: OnceLock<Config> = OnceLock::new();
+
+impl Config {
+    /// Load the config file, if there is one.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        match config_path() {
+            None => Ok(Self::default()),
+            Some(path) => Self::load_from(&path),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        };
+        let config: Self =
+            toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        config
+            .check()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Check the settings that toml can't, like a goal that would have to be divided by.
+    pub fn check(&self) -> Result<(), String> {
+        for (name, minutes) in [
+            ("weekly_goal", self.weekly_goal),
+            ("full_week", self.full_week),
+        ] {
+            if let Some(minutes) = minutes.filter(|&minutes| minutes <= 0) {
+                return Err(format!(
+                    "{} must be more than 0 minutes, not {}",
+                    name, minutes
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Use this config for the rest of the run, instead of loading the config file again. That's
+    /// how overrides from the command line reach the rest of t.
+    pub fn set_up(self) {
+        // Like zone::set_up_local, anything that already needed the config got the same file.
+        let _ = CURRENT.set(self);
+    }
+
+    /// The config that was set up, or the config file if there wasn't one.
+    pub fn current() -> Result<&'static Self, Box<dyn Error>> {
+        if let Some(config) = CURRENT.get() {
+            return Ok(config);
+        }
+        let config = Self::load()?;
+        Ok(CURRENT.get_or_init(|| config))
+    }
+
+    /// The data file is --data-file, or $T_DATA_FILE, or data_file from the config, or ~/.t.csv
+    /// like the Ruby version.
+    pub fn data_file(&self) -> Result<PathBuf, &'static str> {
+        if let Some(path) = &self.data_file_arg {
+            return Ok(path.clone());
+        }
+        if let Some(path) = std::env::var_os("T_DATA_FILE") {
+            return Ok(path.into());
+        }
+        if let Some(path) = &self.data_file {
+            return Ok(expand_home(path));
+        }
+        home_dir()
+            .map(|home| home.join(".t.csv"))
+            .ok_or("T_DATA_FILE or HOME must be set")
+    }
+
+    /// The calendar from the config, with any overrides from the environment.
+    pub fn calendar(&self) -> Result<Calendar, String> {
+        let mut cal = Calendar::default();
+        if let Some(s) = &self.day_rollover {
+            cal.day_rollover = parse_time_of_day(s).map_err(|e| format!("day_rollover: {}", e))?;
+        }
+        if let Some(s) = &self.week_start {
+            cal.week_start = parse_weekday(s).map_err(|e| format!("week_start: {}", e))?;
+        }
+        cal.with_env()
+    }
+}
+
+/// The config file is $T_CONFIG, or t/config.toml in $XDG_CONFIG_HOME or ~/.config.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("T_CONFIG") {
+        return Some(path.into());
+    }
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".config"),
+    };
+    Some(config_dir.join("t").join("config.toml"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+    use time::{time, Weekday};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    fn load(contents: &str) -> Result<Config, Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, contents)?;
+        Config::load_from(&path)
+    }
+
+    #[test]
+    fn test_missing_file() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let config = Config::load_from(&dir.path().join("config.toml"))?;
+        assert_eq!(Config::default(), config);
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_settings() -> TestRes {
+        let config = load(
+            "data_file = \"/tmp/t.csv\"\n\
+             weekly_goal = 1800\n\
+             full_week = 2100\n\
+             sparks = \"_-=#\"\n\
+             week_start = \"monday\"\n\
+             time_zone = \"America/New_York\"\n\
+             day_rollover = \"04:00\"\n\
+             day_legend = \"7h=420m\"\n\
+             week_legend = \"35h=2100m\"\n\
+             backups = 3\n",
+        )?;
+        assert_eq!(
+            Config {
+                data_file: Some(PathBuf::from("/tmp/t.csv")),
+                weekly_goal: Some(1800),
+                full_week: Some(2100),
+                sparks: Some("_-=#".to_string()),
+                week_start: Some("monday".to_string()),
+                time_zone: Some("America/New_York".to_string()),
+                day_rollover: Some("04:00".to_string()),
+                day_legend: Some("7h=420m".to_string()),
+                week_legend: Some("35h=2100m".to_string()),
+                backups: Some(3),
+                data_file_arg: None,
+            },
+            config
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_goals_must_be_positive() -> TestRes {
+        assert!(load("weekly_goal = 0\n").is_err());
+        assert!(load("full_week = -60\n").is_err());
+        assert_eq!(Some(1), load("weekly_goal = 1\n")?.weekly_goal);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(load("weekly_gaol = 1800\n").is_err());
+    }
+
+    #[test]
+    fn test_bad_toml() {
+        assert!(load("weekly_goal = \n").is_err());
+    }
+
+    #[test]
+    fn test_calendar() -> TestRes {
+        let config = Config {
+            week_start: Some("mon".to_string()),
+            day_rollover: Some("03:30".to_string()),
+            ..Config::default()
+        };
+        let cal = config.calendar()?;
+        if std::env::var_os("T_WEEK_START").is_none() {
+            assert_eq!(Weekday::Monday, cal.week_start);
+        }
+        if std::env::var_os("T_DAY_ROLLOVER").is_none() {
+            assert_eq!(time!(03:30), cal.day_rollover);
+        }
+        let config = Config {
+            week_start: Some("someday".to_string()),
+            ..Config::default()
+        };
+        assert!(config.calendar().is_err());
+        Ok(())
+    }
+}
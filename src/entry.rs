@@ -1,4 +1,5 @@
 use crate::timesource::TimeSource;
+use crate::zone::Zone;
 use std::clone::Clone;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
@@ -11,7 +12,7 @@ pub fn into_time_entries(entries: Vec<Entry>) -> Vec<TimeEntry> {
         .collect()
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Entry {
     Time(TimeEntry),
     Note(String),
@@ -55,25 +56,68 @@ impl Display for Entry {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Self::Time(te) => te.fmt(f),
-            Self::Note(s) => write!(f, "# {s}"),
+            Self::Note(s) => writeln!(f, "#{s}"),
         }
     }
 }
 
+/// Marks an entry that continues the session of the entry before it, i.e. the time between them
+/// was a pause rather than a switch to something else.
+pub const RESUME_MARKER: &str = "resume";
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct TimeEntry {
     pub start: Time,
     pub stop: Option<Time>,
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub resumed: bool,
 }
 
 impl TimeEntry {
     pub fn start<TS: TimeSource>(ts: &TS) -> Self {
+        Self::start_at(ts.now())
+    }
+
+    pub fn start_at(start: OffsetDateTime) -> Self {
         Self {
-            start: Time::at(ts.now()),
+            start: Time::at(start),
             stop: None,
+            label: None,
+            notes: vec![],
+            resumed: false,
+        }
+    }
+
+    pub fn with_label(self, label: Option<String>) -> Self {
+        Self { label, ..self }
+    }
+
+    pub fn with_note(mut self, note: Option<String>) -> Self {
+        self.notes.extend(note);
+        self
+    }
+
+    pub fn resuming(self) -> Self {
+        Self {
+            resumed: true,
+            ..self
+        }
+    }
+
+    /// Write the UTC offset on the start and stop times even if the file didn't have them.
+    pub fn with_explicit_tz(self) -> Self {
+        Self {
+            start: self.start.with_explicit_tz(),
+            stop: self.stop.map(Time::with_explicit_tz),
+            ..self
         }
     }
 
+    pub fn has_label(&self, label: &str) -> bool {
+        self.label.as_deref() == Some(label)
+    }
+
     pub fn finish_if_not(self, stop: OffsetDateTime) -> Self {
         if self.is_finished() {
             self
@@ -85,11 +129,15 @@ impl TimeEntry {
         }
     }
     pub fn finish<TS: TimeSource>(self, ts: &TS) -> Self {
+        self.finish_at(ts.now())
+    }
+
+    pub fn finish_at(self, stop: OffsetDateTime) -> Self {
         if self.is_finished() {
             panic!("finish called for a finished entry! {}", self);
         }
         Self {
-            stop: Some(Time::at(ts.now())),
+            stop: Some(Time::at(stop)),
             ..self
         }
     }
@@ -212,11 +260,27 @@ impl TimeEntry {
 impl Display for TimeEntry {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.start)?;
-        if let Some(stop) = &self.stop {
-            writeln!(f, ",{}", stop)
-        } else {
-            writeln!(f)
+        let fields = [
+            self.stop
+                .as_ref()
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            self.label.clone().unwrap_or_default(),
+            if self.resumed { RESUME_MARKER } else { "" }.to_string(),
+        ];
+        // Trailing empty fields are left off.
+        let len = fields
+            .iter()
+            .rposition(|s| !s.is_empty())
+            .map_or(0, |i| i + 1);
+        for field in &fields[..len] {
+            write!(f, ",{}", field)?;
+        }
+        writeln!(f)?;
+        for note in &self.notes {
+            writeln!(f, "  # {}", note)?;
         }
+        Ok(())
     }
 }
 
@@ -228,13 +292,14 @@ pub struct Time {
 
 pub enum TZ {
     Known(time::UtcOffset),
-    Implied(time::UtcOffset),
+    /// The offset isn't written down, so it's whatever it was in the zone at the time.
+    Implied(Zone),
 }
 
 impl TZ {
     pub fn from<TS: TimeSource>(utc_offset: Option<i16>, ts: &TS) -> Self {
         match utc_offset {
-            None => Self::Implied(ts.local_offset()),
+            None => Self::Implied(ts.zone()),
             Some(minutes) => Self::Known(time::UtcOffset::minutes(minutes)),
         }
     }
@@ -264,8 +329,8 @@ impl Time {
     fn from_dto(date: time::Date, time: time::Time, tz: TZ) -> Self {
         let dt = PrimitiveDateTime::new(date, time);
         match tz {
-            TZ::Implied(off) => Self {
-                wrapped: dt.assume_offset(off),
+            TZ::Implied(zone) => Self {
+                wrapped: zone.assume(dt),
                 implied_tz: true,
             },
             TZ::Known(off) => Self {
@@ -275,6 +340,13 @@ impl Time {
         }
     }
 
+    pub fn with_explicit_tz(self) -> Self {
+        Self {
+            implied_tz: false,
+            ..self
+        }
+    }
+
     fn year(&self) -> i32 {
         self.wrapped.year()
     }
@@ -283,10 +355,20 @@ impl Time {
         self.wrapped.month()
     }
 
+    pub fn datetime(&self) -> OffsetDateTime {
+        self.wrapped
+    }
+
     pub fn date(&self) -> time::Date {
         self.wrapped.date()
     }
 
+    /// Whether the time was written without a UTC offset, so it's in whatever the local time zone
+    /// was when it was read.
+    pub fn has_implied_tz(&self) -> bool {
+        self.implied_tz
+    }
+
     pub fn time(&self) -> time::Time {
         self.wrapped.time()
     }
@@ -337,6 +419,9 @@ mod tests {
         let entry = TimeEntry {
             start: Time::new(2020, 6, 20, 1, 7, TZ::from(None, &DefaultTimeSource))?,
             stop: None,
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         assert_eq!("2020-06-20 01:07\n", format!("{}", entry));
         Ok(())
@@ -354,11 +439,73 @@ mod tests {
                 8,
                 TZ::from(None, &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         assert_eq!("2020-06-20 01:07,2020-06-20 01:08\n", format!("{}", entry));
         Ok(())
     }
 
+    #[test]
+    fn test_entry_format_with_label() -> TestRes {
+        let start = Time::new(2020, 6, 20, 1, 7, TZ::from(None, &DefaultTimeSource))?;
+        let stop = Time::new(2020, 6, 20, 1, 8, TZ::from(None, &DefaultTimeSource))?;
+        let entry = TimeEntry {
+            start,
+            stop: None,
+            label: Some("acme".to_string()),
+            notes: vec![],
+            resumed: false,
+        };
+        assert_eq!("2020-06-20 01:07,,acme\n", format!("{}", entry));
+        let entry = TimeEntry {
+            stop: Some(stop),
+            ..entry
+        };
+        assert_eq!(
+            "2020-06-20 01:07,2020-06-20 01:08,acme\n",
+            format!("{}", entry)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_format_resumed() -> TestRes {
+        let start = Time::new(2020, 6, 20, 1, 7, TZ::from(None, &DefaultTimeSource))?;
+        let stop = Time::new(2020, 6, 20, 1, 8, TZ::from(None, &DefaultTimeSource))?;
+        let entry = TimeEntry::start_at(start.datetime()).resuming();
+        assert_eq!("2020-06-20 01:07 +0000,,,resume\n", format!("{}", entry));
+        let entry = TimeEntry {
+            start,
+            stop: Some(stop),
+            label: Some("acme".to_string()),
+            notes: vec![],
+            resumed: true,
+        };
+        assert_eq!(
+            "2020-06-20 01:07,2020-06-20 01:08,acme,resume\n",
+            format!("{}", entry)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_format_with_notes() -> TestRes {
+        let entry = TimeEntry {
+            start: Time::new(2020, 6, 20, 1, 7, TZ::from(None, &DefaultTimeSource))?,
+            stop: None,
+            label: None,
+            notes: vec!["first".to_string(), "second".to_string()],
+            resumed: false,
+        };
+        assert_eq!(
+            "2020-06-20 01:07\n  # first\n  # second\n",
+            format!("{}", entry)
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_at() {
         let ts = mock_time(date!(2020 - 07 - 15), time!(11:23), offset!(+11:00));
@@ -378,6 +525,9 @@ mod tests {
                 8,
                 TZ::from(None, &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         assert_eq!(1, entry.minutes(&DefaultTimeSource));
         Ok(())
@@ -389,6 +539,9 @@ mod tests {
         let entry = TimeEntry {
             start: Time::new(2020, 6, 20, 1, 7, TZ::from(Some(120), &ts))?,
             stop: None,
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         assert_eq!(4 * 60 + 48, entry.minutes(&ts));
         Ok(())
@@ -400,6 +553,9 @@ mod tests {
         let entry = TimeEntry {
             start: Time::new(2020, 6, 20, 1, 7, TZ::from(None, &ts))?,
             stop: None,
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         assert_eq!(48, entry.minutes(&ts));
         Ok(())
@@ -417,6 +573,9 @@ mod tests {
                 0,
                 TZ::from(Some(0), &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(0:00)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 21), time!(0:00)).assume_utc();
@@ -436,6 +595,9 @@ mod tests {
                 0,
                 TZ::from(Some(0), &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 19), time!(0:00)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(0:00)).assume_utc();
@@ -455,6 +617,9 @@ mod tests {
                 0,
                 TZ::from(Some(0), &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 21), time!(0:00)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 22), time!(0:00)).assume_utc();
@@ -474,6 +639,9 @@ mod tests {
                 0,
                 TZ::from(Some(0), &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(0:00)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(9:30)).assume_utc();
@@ -493,6 +661,9 @@ mod tests {
                 0,
                 TZ::from(Some(0), &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(9:30)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 21), time!(0:00)).assume_utc();
@@ -512,6 +683,9 @@ mod tests {
                 0,
                 TZ::from(Some(0), &DefaultTimeSource),
             )?),
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(9:10)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(9:50)).assume_utc();
@@ -524,6 +698,9 @@ mod tests {
         let entry = TimeEntry {
             start: Time::new(2020, 6, 20, 9, 0, TZ::from(Some(0), &DefaultTimeSource))?,
             stop: None,
+            label: None,
+            notes: vec![],
+            resumed: false,
         };
         let start = PrimitiveDateTime::new(date!(2020 - 06 - 20), time!(0:00)).assume_utc();
         let stop = PrimitiveDateTime::new(date!(2020 - 06 - 21), time!(0:00)).assume_utc();
@@ -0,0 +1,470 @@
+use crate::entry::{Entry, Time, TimeEntry, TZ};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{self, Write};
+use std::str::FromStr;
+use time::{OffsetDateTime, UtcOffset};
+
+/// Format is a kind of file that `t export` can write.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// An iCalendar file with an event for each finished entry.
+    Ics,
+    /// A JSON array with every entry and note.
+    Json,
+    /// The same as JSON, but with each entry on a line by itself.
+    Ndjson,
+    /// Check-ins and check-outs in the timeclock format that hledger and ledger read.
+    Timeclock,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ics" => Ok(Self::Ics),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "timeclock" => Ok(Self::Timeclock),
+            _ => Err(format!(
+                "can't export to {s:?}, only to ics, json, ndjson or timeclock"
+            )),
+        }
+    }
+}
+
+/// The entries that overlap the time from `start` to `stop`, along with where each one is in the
+/// whole history. A note that isn't attached to an entry goes along with the entry before it.
+pub fn between(
+    entries: Vec<Entry>,
+    start: OffsetDateTime,
+    stop: OffsetDateTime,
+) -> Vec<(usize, Entry)> {
+    let mut keep = false;
+    let mut selected = vec![];
+    for (position, entry) in entries.into_iter().enumerate() {
+        if let Entry::Time(te) = &entry {
+            keep = te.overlaps(start, stop);
+        }
+        if keep {
+            selected.push((position, entry));
+        }
+    }
+    selected
+}
+
+/// JsonEntry is what an entry looks like in `t export json`, and what `t import --from json`
+/// reads. Only the time and whether the offset was implied are needed to import a time, and an
+/// entry's position is left out of imports, which go wherever they fit.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JsonEntry {
+    Time {
+        /// Where the entry is in the whole history, counting from 0.
+        #[serde(default)]
+        position: usize,
+        start: JsonTime,
+        #[serde(default)]
+        stop: Option<JsonTime>,
+        #[serde(default)]
+        label: Option<String>,
+        #[serde(default)]
+        resumed: bool,
+        #[serde(default)]
+        notes: Vec<String>,
+    },
+    Note {
+        #[serde(default)]
+        position: usize,
+        text: String,
+    },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct JsonTime {
+    /// An RFC 3339 time, e.g. 2023-01-02T09:00:00-05:00.
+    pub time: String,
+    /// The UTC offset, e.g. -05:00.
+    #[serde(default)]
+    pub offset: String,
+    /// Whether t.csv leaves the offset off, because it's the local one.
+    #[serde(default)]
+    pub implied_tz: bool,
+}
+
+impl JsonEntry {
+    pub fn new(position: usize, entry: &Entry) -> Self {
+        match entry {
+            Entry::Time(te) => Self::Time {
+                position,
+                start: JsonTime::new(&te.start),
+                stop: te.stop.as_ref().map(JsonTime::new),
+                label: te.label.clone(),
+                resumed: te.resumed,
+                notes: te.notes.clone(),
+            },
+            Entry::Note(text) => Self::Note {
+                position,
+                text: text.clone(),
+            },
+        }
+    }
+
+    pub fn into_entry(self) -> Result<Entry, String> {
+        match self {
+            Self::Time {
+                start,
+                stop,
+                label,
+                resumed,
+                notes,
+                ..
+            } => Ok(Entry::Time(TimeEntry {
+                start: start.into_time()?,
+                stop: stop.map(JsonTime::into_time).transpose()?,
+                label,
+                notes,
+                resumed,
+            })),
+            Self::Note { text, .. } => Ok(Entry::Note(text)),
+        }
+    }
+}
+
+impl JsonTime {
+    fn new(t: &Time) -> Self {
+        let offset = t.datetime().offset();
+        let minutes = offset.as_minutes().abs();
+        Self {
+            time: t.datetime().format(time::Format::Rfc3339),
+            offset: format!(
+                "{}{:02}:{:02}",
+                if offset.as_seconds() < 0 { '-' } else { '+' },
+                minutes / 60,
+                minutes % 60
+            ),
+            implied_tz: t.has_implied_tz(),
+        }
+    }
+
+    // t.csv only has whole minutes. The time crate reads the offset in RFC 3339 as minutes, so it
+    // gets turned into one that %z understands first.
+    fn into_time(self) -> Result<Time, String> {
+        let s = &self.time;
+        let offset = match s.strip_suffix('Z') {
+            Some(rest) => Some((rest, "+0000".to_string())),
+            None => s
+                .len()
+                .checked_sub(6)
+                .filter(|&i| s.is_char_boundary(i))
+                .map(|i| (&s[..i], s[i..].replacen(':', "", 1))),
+        };
+        let dt = offset
+            .and_then(|(rest, offset)| {
+                OffsetDateTime::parse(format!("{rest}{offset}"), "%Y-%m-%dT%H:%M:%S%z").ok()
+            })
+            .ok_or_else(|| format!("expected a time like 2023-01-02T09:00:00-05:00, not {s:?}"))?;
+        let tz = if self.implied_tz {
+            TZ::Implied(dt.offset().into())
+        } else {
+            TZ::Known(dt.offset())
+        };
+        Time::new(
+            dt.year() as u16,
+            dt.month(),
+            dt.day(),
+            dt.hour(),
+            dt.minute(),
+            tz,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Write the entries as a JSON array.
+pub fn write_json<W: Write>(w: &mut W, entries: &[(usize, Entry)]) -> Result<(), Box<dyn Error>> {
+    let records: Vec<JsonEntry> = entries
+        .iter()
+        .map(|(position, entry)| JsonEntry::new(*position, entry))
+        .collect();
+    serde_json::to_writer_pretty(&mut *w, &records)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Write each entry as JSON on a line by itself.
+pub fn write_ndjson<W: Write>(w: &mut W, entries: &[(usize, Entry)]) -> Result<(), Box<dyn Error>> {
+    for (position, entry) in entries {
+        serde_json::to_writer(&mut *w, &JsonEntry::new(*position, entry))?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Write the finished entries as an iCalendar file. Each event's UID comes from when the entry
+/// started, so importing a newer export into a calendar updates the events that are already there.
+pub fn write_ics<W: Write>(w: &mut W, entries: &[TimeEntry]) -> io::Result<()> {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//t//time entries//EN".to_string(),
+    ];
+    for entry in entries {
+        let stop = match &entry.stop {
+            Some(stop) => stop,
+            None => continue,
+        };
+        let start = utc(&entry.start);
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{start}@t"));
+        // The stop time stands in for when the event was last changed, so that exporting the
+        // same entries again gives the same file.
+        lines.push(format!("DTSTAMP:{}", utc(stop)));
+        lines.push(format!("DTSTART:{start}"));
+        lines.push(format!("DTEND:{}", utc(stop)));
+        lines.push(format!(
+            "SUMMARY:{}",
+            escape(entry.label.as_deref().unwrap_or("Work"))
+        ));
+        if !entry.notes.is_empty() {
+            let notes: Vec<&str> = entry.notes.iter().map(|note| note.trim()).collect();
+            lines.push(format!("DESCRIPTION:{}", escape(&notes.join("\n"))));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    for line in lines {
+        write!(w, "{}\r\n", fold(&line))?;
+    }
+    Ok(())
+}
+
+/// Write the finished entries as timeclock check-ins and check-outs, like
+///   i 2023/01/02 09:00:00 acme  code review
+///   o 2023/01/02 10:30:00
+/// The label is the account and the notes are the description. An entry without a label uses its
+/// notes as the account instead. Timeclock times have no UTC offset, so both are written in the
+/// offset the entry started in, and the time between them stays the same.
+pub fn write_timeclock<W: Write>(w: &mut W, entries: &[TimeEntry]) -> io::Result<()> {
+    for entry in entries {
+        let stop = match &entry.stop {
+            Some(stop) => stop,
+            None => continue,
+        };
+        let start = entry.start.datetime();
+        let format = "%Y/%m/%d %H:%M:%S";
+        let notes = words(entry.notes.iter().map(String::as_str));
+        let (account, description) = match &entry.label {
+            Some(label) => (words(std::iter::once(label.as_str())), notes),
+            None => (notes, String::new()),
+        };
+        write!(w, "i {}", start.format(format))?;
+        if !account.is_empty() {
+            write!(w, " {account}")?;
+            if !description.is_empty() {
+                write!(w, "  {description}")?;
+            }
+        }
+        writeln!(w)?;
+        writeln!(
+            w,
+            "o {}",
+            stop.datetime().to_offset(start.offset()).format(format)
+        )?;
+    }
+    Ok(())
+}
+
+// Two spaces end a timeclock account, so everything is put on one line with single spaces.
+fn words<'a>(s: impl Iterator<Item = &'a str>) -> String {
+    s.flat_map(str::split_whitespace)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn utc(t: &Time) -> String {
+    t.datetime()
+        .to_offset(UtcOffset::UTC)
+        .format("%Y%m%dT%H%M%SZ")
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Lines can be at most 75 bytes long. Longer ones continue on the next line after a space,
+// without splitting a character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{between, write_ics, write_ndjson, write_timeclock, JsonEntry};
+    use crate::entry::{into_time_entries, Entry, TimeEntry};
+    use crate::import;
+    use crate::parser::{parse_entries, parse_time_entries, write_entries};
+    use crate::timesource::mock_time::mock_time;
+    use pretty_assertions::assert_eq;
+    use time::{date, offset, time};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_write_ics() -> TestRes {
+        let ts = mock_time(date!(2023 - 01 - 02), time!(18:00), offset!(-05:00));
+        let input = "2023-01-02 09:00 -0500,2023-01-02 10:30 -0500,acme\n\
+                     \x20 # reviewed a PR; it's fine, mostly\n\
+                     \x20 # and a second note that's long enough that the line has to be folded\n\
+                     2023-01-02 13:00 +0100,2023-01-02 14:00 +0100\n\
+                     2023-01-02 17:00 -0500\n";
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let mut output = vec![];
+        write_ics(&mut output, &entries)?;
+        assert_eq!(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//t//time entries//EN\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:20230102T140000Z@t\r\n\
+             DTSTAMP:20230102T153000Z\r\n\
+             DTSTART:20230102T140000Z\r\n\
+             DTEND:20230102T153000Z\r\n\
+             SUMMARY:acme\r\n\
+             DESCRIPTION:reviewed a PR\\; it's fine\\, mostly\\nand a second note that's lo\r\n ng enough that the line has to be folded\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:20230102T120000Z@t\r\n\
+             DTSTAMP:20230102T130000Z\r\n\
+             DTSTART:20230102T120000Z\r\n\
+             DTEND:20230102T130000Z\r\n\
+             SUMMARY:Work\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n",
+            String::from_utf8(output)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_round_trip() -> TestRes {
+        let ts = mock_time(date!(2023 - 01 - 02), time!(18:00), offset!(-05:00));
+        let input = "# a free note\n\
+                     2023-01-02 09:00,2023-01-02 10:30 +0100,acme\n\
+                     \x20 # attached\n\
+                     2023-01-02 13:00 -0500,2023-01-02 14:00 -0500,acme,resume\n\
+                     2023-01-02 17:00 -0500\n";
+        let entries = parse_entries(input.as_bytes(), &ts)?;
+        let selected: Vec<(usize, Entry)> = entries.into_iter().enumerate().collect();
+        let mut output = vec![];
+        write_ndjson(&mut output, &selected)?;
+        let output = String::from_utf8(output)?;
+        assert_eq!(
+            "{\"type\":\"note\",\"position\":0,\"text\":\" a free note\"}",
+            output.lines().next().unwrap()
+        );
+        assert_eq!(
+            "{\"type\":\"time\",\"position\":1,\
+             \"start\":{\"time\":\"2023-01-02T09:00:00-05:00\",\"offset\":\"-05:00\",\"implied_tz\":true},\
+             \"stop\":{\"time\":\"2023-01-02T10:30:00+01:00\",\"offset\":\"+01:00\",\"implied_tz\":false},\
+             \"label\":\"acme\",\"resumed\":false,\"notes\":[\"attached\"]}",
+            output.lines().nth(1).unwrap()
+        );
+
+        let mut entries = vec![];
+        for line in output.lines() {
+            entries.push(serde_json::from_str::<JsonEntry>(line)?.into_entry()?);
+        }
+        let mut written = vec![];
+        write_entries(&mut written, &entries)?;
+        assert_eq!(input, String::from_utf8(written)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_between() -> TestRes {
+        let ts = mock_time(date!(2023 - 01 - 03), time!(18:00), offset!(-05:00));
+        let input = "# before\n\
+                     2023-01-02 09:00 -0500,2023-01-02 10:00 -0500\n\
+                     # about the 2nd\n\
+                     2023-01-03 09:00 -0500,2023-01-03 10:00 -0500\n\
+                     # about the 3rd\n";
+        let entries = parse_entries(input.as_bytes(), &ts)?;
+        let start = date!(2023 - 01 - 03)
+            .midnight()
+            .assume_offset(offset!(-05:00));
+        let selected = between(entries, start, start + time::Duration::day());
+        let positions: Vec<usize> = selected.iter().map(|(position, _)| *position).collect();
+        assert_eq!(vec![3, 4], positions);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeclock_round_trip() -> TestRes {
+        let ts = mock_time(date!(2023 - 01 - 02), time!(18:00), offset!(-05:00));
+        let input = "2023-01-02 09:00 -0500,2023-01-02 16:30 +0100,acme:reviews\n\
+                     \x20 # reviewed  a PR\n\
+                     \x20 # and another\n\
+                     2023-01-02 11:00 -0500,2023-01-02 11:45 -0500\n\
+                     \x20 # lunch, early\n\
+                     2023-01-02 12:00 -0500,2023-01-02 12:10 -0500\n\
+                     2023-01-02 17:00 -0500\n";
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let mut output = vec![];
+        write_timeclock(&mut output, &entries)?;
+        let output = String::from_utf8(output)?;
+        assert_eq!(
+            "i 2023/01/02 09:00:00 acme:reviews  reviewed a PR and another\n\
+             o 2023/01/02 10:30:00\n\
+             i 2023/01/02 11:00:00 lunch, early\n\
+             o 2023/01/02 11:45:00\n\
+             i 2023/01/02 12:00:00\n\
+             o 2023/01/02 12:10:00\n",
+            output
+        );
+
+        let foreign = import::read(
+            output.as_bytes(),
+            import::Format::Timeclock,
+            &offset!(-05:00).into(),
+        )?;
+        assert!(foreign.skipped.is_empty());
+        let imported = into_time_entries(foreign.entries);
+        let minutes = |entries: &[TimeEntry]| -> Vec<i64> {
+            entries
+                .iter()
+                .filter_map(|te| {
+                    Some((te.stop.as_ref()?.datetime() - te.start.datetime()).whole_minutes())
+                })
+                .collect()
+        };
+        assert_eq!(vec![90, 45, 10], minutes(&imported));
+        assert_eq!(minutes(&entries), minutes(&imported));
+        assert_eq!(Some("acme:reviews".to_string()), imported[0].label);
+        assert_eq!(vec!["lunch, early".to_string()], imported[1].notes);
+        Ok(())
+    }
+}
@@ -1,21 +1,21 @@
-use time::{Duration, OffsetDateTime, Weekday::Sunday};
+use crate::calendar::Calendar;
+use crate::timesource::real_time::DefaultTimeSource;
+use crate::timesource::TimeSource;
+use time::OffsetDateTime;
 
-pub fn today() -> (OffsetDateTime, OffsetDateTime) {
-    let now = OffsetDateTime::now_local();
-    let start_today = now.date().midnight().assume_offset(now.offset());
+pub fn today(cal: &Calendar) -> (OffsetDateTime, OffsetDateTime) {
+    let now = DefaultTimeSource.now();
+    let start_today = cal.start_of_day(cal.workday(now), &DefaultTimeSource.zone());
     (start_today, now)
 }
 
-pub fn this_week() -> (OffsetDateTime, OffsetDateTime) {
-    let now = OffsetDateTime::now_local();
-    let start = start_of_week(&now);
+pub fn this_week(cal: &Calendar) -> (OffsetDateTime, OffsetDateTime) {
+    let now = DefaultTimeSource.now();
+    let start = start_of_week(cal, &now);
     (start, now)
 }
 
-fn start_of_week(dt: &OffsetDateTime) -> OffsetDateTime {
-    if dt.weekday() == Sunday {
-        dt.date().midnight().assume_offset(dt.offset())
-    } else {
-        start_of_week(&(*dt - Duration::day()))
-    }
+fn start_of_week(cal: &Calendar, dt: &OffsetDateTime) -> OffsetDateTime {
+    let date = cal.start_of_week(cal.workday(*dt));
+    cal.start_of_day(date, &DefaultTimeSource.zone())
 }
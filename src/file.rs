@@ -1,18 +1,31 @@
-use crate::entry::{into_time_entries, Entry, TimeEntry};
-use crate::parser::{parse_entries, parse_entry};
+use crate::archive;
+use crate::backup::{self, Backup};
+use crate::cache::{self, Totals};
+use crate::calendar::Calendar;
+use crate::config::Config;
+use crate::entry::{into_time_entries, Entry, Time, TimeEntry, RESUME_MARKER};
+use crate::journal::{Change, Journal};
+use crate::lock::{lock_exclusive, lock_shared, DataLock};
+use crate::parser::{
+    entries, parse_entries, parse_entries_recovering, parse_entry, write_entries, ParseError,
+    Parsed,
+};
+use crate::repair::{repair, Fix, Repairs};
 use crate::timesource::TimeSource;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt::Display;
 use std::fs::{File, OpenOptions};
 use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
-use std::path::Path;
-
-const APPROX_LINE_LENGTH_FOR_SEEK: u64 = 50;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
 
 #[cfg(test)]
 mod tests {
     use crate::entry::Entry;
     use crate::timesource::mock_time::mock_time;
     use crate::timesource::real_time::DefaultTimeSource;
+    use crate::timesource::TimeSource;
     use std::error::Error;
     use std::fs::File;
     use std::io::Read;
@@ -65,7 +78,10 @@ mod tests {
     fn test_start_in_new_file() -> TestRes {
         let ts = mock_time(date!(2020 - 07 - 15), time!(10:23), offset!(-04:00));
         let fixt = Fixture::new(None)?;
-        assert_eq!(None, super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         assert_eq!("2020-07-15 10:23 -0400\n", fixt.read()?);
         Ok(())
     }
@@ -74,7 +90,10 @@ mod tests {
     fn test_start_in_empty_file() -> TestRes {
         let ts = mock_time(date!(2020 - 07 - 15), time!(10:23), offset!(-04:00));
         let fixt = Fixture::new(Some("empty.csv"))?;
-        assert_eq!(None, super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         assert_eq!("2020-07-15 10:23 -0400\n", fixt.read()?);
         Ok(())
     }
@@ -83,7 +102,10 @@ mod tests {
     fn test_start_in_file_with_entries() -> TestRes {
         let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
         let fixt = Fixture::new(Some("three-entries.csv"))?;
-        assert_eq!(None, super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         assert_eq!(
             "2020-08-06 15:38 -0400,2020-08-06 18:40 -0400\n\
                     2020-08-07 11:03 -0400,2020-08-07 13:07 -0400\n\
@@ -94,14 +116,36 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_start_through_symlink() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let link = fixt.dir.path().join("link.csv");
+        std::os::unix::fs::symlink("test-t.csv", &link)?;
+        super::_start_new_entry(&link, None, None, &ts)?;
+        assert!(std::fs::symlink_metadata(&link)?.file_type().is_symlink());
+        assert!(fixt.read()?.ends_with("\n2020-08-08 10:23 -0400\n"));
+        Ok(())
+    }
+
     #[test]
     fn test_start_twice() -> TestRes {
         let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
         let fixt = Fixture::new(None)?;
-        assert_eq!(None, super::_start_new_entry(fixt.t_data_file(), &ts)?);
-        assert_eq!(Some(0), super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
+        assert_eq!(
+            Some(0),
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         let ts = mock_time(date!(2020 - 08 - 08), time!(10:34), offset!(-04:00));
-        assert_eq!(Some(11), super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            Some(11),
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         assert_eq!("2020-08-08 10:23 -0400\n", fixt.read()?);
         Ok(())
     }
@@ -110,7 +154,10 @@ mod tests {
     fn test_start_with_blank_lines() -> TestRes {
         let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
         let fixt = Fixture::new(Some("blank-lines.csv"))?;
-        assert_eq!(None, super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         assert_eq!("2020-08-08 10:23 -0400\n", fixt.read()?);
         Ok(())
     }
@@ -119,7 +166,10 @@ mod tests {
     fn test_start_with_entry_and_trailing_whitespace() -> TestRes {
         let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
         let fixt = Fixture::new(Some("entry-with-trailing-blank-lines.csv"))?;
-        assert_eq!(None, super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
         assert_eq!(
             "2020-08-06 14:00 -0400,2020-08-06 17:55 -0400\n\
                     \n\
@@ -136,7 +186,7 @@ mod tests {
         let fixt = Fixture::new(Some("started-with-comma.csv"))?;
         assert_eq!(
             Some(1223),
-            super::_start_new_entry(fixt.t_data_file(), &ts)?
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
         );
         Ok(())
     }
@@ -147,7 +197,7 @@ mod tests {
         let fixt = Fixture::new(Some("started-no-comma.csv"))?;
         assert_eq!(
             Some(1223),
-            super::_start_new_entry(fixt.t_data_file(), &ts)?
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
         );
         Ok(())
     }
@@ -156,7 +206,176 @@ mod tests {
     fn test_start_when_two_entries_are_pending() -> TestRes {
         let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
         let fixt = Fixture::new(Some("two-started-entries.csv"))?;
-        assert_eq!(Some(83), super::_start_new_entry(fixt.t_data_file(), &ts)?);
+        assert_eq!(
+            Some(83),
+            super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_with_label() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), Some("acme".to_string()), None, &ts)?
+        );
+        assert_eq!(
+            "2020-08-06 15:38 -0400,2020-08-06 18:40 -0400\n\
+                    2020-08-07 11:03 -0400,2020-08-07 13:07 -0400\n\
+                    2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n\
+                    2020-08-08 10:23 -0400,,acme\n",
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_with_bad_label() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let before = fixt.read()?;
+        let label = Some("acme, resume".to_string());
+        assert!(super::_start_new_entry(fixt.t_data_file(), label, None, &ts).is_err());
+        assert_eq!(before, fixt.read()?);
+        let label = Some("acme, initech".to_string());
+        super::_start_new_entry(fixt.t_data_file(), label, None, &ts)?;
+        assert_eq!(
+            before + "2020-08-08 10:23 -0400,,acme, initech\n",
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause_and_resume() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(09:00), offset!(-04:00));
+        super::_start_new_entry(fixt.t_data_file(), Some("acme".to_string()), None, &ts)?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(12:00), offset!(-04:00));
+        super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(12:45), offset!(-04:00));
+        assert_eq!(
+            None,
+            super::_resume_last_entry(fixt.t_data_file(), None, &ts)?
+        );
+        assert!(fixt.read()?.ends_with(
+            "\n2020-08-08 09:00 -0400,2020-08-08 12:00 -0400,acme\n\
+             2020-08-08 12:45 -0400,,acme,resume\n"
+        ));
+        let ts = mock_time(date!(2020 - 08 - 08), time!(13:00), offset!(-04:00));
+        assert_eq!(
+            Some(15),
+            super::_resume_last_entry(fixt.t_data_file(), None, &ts)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch() -> TestRes {
+        let fixt = Fixture::new(Some("started-with-note.csv"))?;
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
+        assert_eq!(
+            Some(83),
+            super::_switch_entry(fixt.t_data_file(), Some("initech".to_string()), None, &ts)?
+        );
+        assert_eq!(
+            "2020-08-06 14:00 -0400,2020-08-06 17:55 -0400\n\
+             2020-08-07 14:00 -0400,2020-08-07 15:23 -0400,acme\n\
+             \x20 # a note long enough to push the entry past the first guess of where to look\n\
+             2020-08-07 15:23 -0400,,initech\n",
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_when_stopped() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        assert_eq!(
+            None,
+            super::_switch_entry(fixt.t_data_file(), Some("acme".to_string()), None, &ts)?
+        );
+        assert!(fixt
+            .read()?
+            .ends_with("17:55 -0400\n2020-08-08 10:23 -0400,,acme\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_at_before_start() -> TestRes {
+        let fixt = Fixture::new(Some("started-with-label.csv"))?;
+        let before = fixt.read()?;
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 07), time!(13:00), offset!(-04:00)).now();
+        assert!(super::_switch_entry(fixt.t_data_file(), None, Some(at), &ts).is_err());
+        assert_eq!(before, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_in_new_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        assert!(super::_resume_last_entry(fixt.t_data_file(), None, &DefaultTimeSource).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_at() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 08), time!(09:15), offset!(-04:00)).now();
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        assert_eq!(
+            None,
+            super::_start_new_entry(fixt.t_data_file(), None, Some(at), &ts)?
+        );
+        assert!(fixt.read()?.ends_with("\n2020-08-08 09:15 -0400\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_at_overlaps_previous_entry() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 07), time!(17:00), offset!(-04:00)).now();
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let before = fixt.read()?;
+        assert!(super::_start_new_entry(fixt.t_data_file(), None, Some(at), &ts).is_err());
+        assert_eq!(before, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_at_overlaps_entry_before_free_note() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 08), time!(09:00), offset!(-04:00)).now();
+        let fixt = Fixture::new(None)?;
+        let contents = "2020-08-08 08:00 -0400,2020-08-08 10:00 -0400\n# free note\n";
+        std::fs::write(fixt.t_data_file(), contents)?;
+        assert!(super::_start_new_entry(fixt.t_data_file(), None, Some(at), &ts).is_err());
+        assert!(super::_switch_entry(fixt.t_data_file(), None, Some(at), &ts).is_err());
+        assert!(super::_resume_last_entry(fixt.t_data_file(), Some(at), &ts).is_err());
+        assert_eq!(contents, fixt.read()?);
+
+        let at = mock_time(date!(2020 - 08 - 08), time!(10:15), offset!(-04:00)).now();
+        assert_eq!(
+            None,
+            super::_resume_last_entry(fixt.t_data_file(), Some(at), &ts)?
+        );
+        assert_eq!(
+            format!("{}2020-08-08 10:15 -0400,,,resume\n", contents),
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_at_in_the_future() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 08), time!(10:24), offset!(-04:00)).now();
+        let fixt = Fixture::new(None)?;
+        assert!(super::_start_new_entry(fixt.t_data_file(), None, Some(at), &ts).is_err());
         Ok(())
     }
 
@@ -165,7 +384,7 @@ mod tests {
         let fixt = Fixture::new(None)?;
         assert_eq!(
             None,
-            super::_stop_current_entry(fixt.t_data_file(), &DefaultTimeSource)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &DefaultTimeSource)?
         );
         Ok(())
     }
@@ -175,7 +394,7 @@ mod tests {
         let fixt = Fixture::new(Some("empty.csv"))?;
         assert_eq!(
             None,
-            super::_stop_current_entry(fixt.t_data_file(), &DefaultTimeSource)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &DefaultTimeSource)?
         );
         Ok(())
     }
@@ -186,7 +405,7 @@ mod tests {
         let fixt = Fixture::new(Some("three-entries.csv"))?;
         assert_eq!(
             Some((false, 328)),
-            super::_stop_current_entry(fixt.t_data_file(), &ts)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
         );
         Ok(())
     }
@@ -197,7 +416,7 @@ mod tests {
         let fixt = Fixture::new(Some("started-with-comma.csv"))?;
         assert_eq!(
             Some((true, 83)),
-            super::_stop_current_entry(fixt.t_data_file(), &ts)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
         );
         assert_eq!(
             "2020-08-07 14:00 -0400,2020-08-07 15:23 -0400\n",
@@ -212,7 +431,7 @@ mod tests {
         let fixt = Fixture::new(Some("started-no-comma.csv"))?;
         assert_eq!(
             Some((true, 83)),
-            super::_stop_current_entry(fixt.t_data_file(), &ts)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
         );
         assert_eq!(
             "2020-08-07 14:00 -0400,2020-08-07 15:23 -0400\n",
@@ -227,226 +446,1590 @@ mod tests {
         let fixt = Fixture::new(Some("started-trailing-blank-lines.csv"))?;
         assert_eq!(
             Some((true, 83)),
-            super::_stop_current_entry(fixt.t_data_file(), &ts)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
         );
         assert_eq!("2020-08-07 14:00,2020-08-07 15:23 -0400\n", fixt.read()?);
         Ok(())
     }
 
     #[test]
-    fn test_stop_when_two_entries_are_pending() -> TestRes {
+    fn test_stop_keeps_label() -> TestRes {
         let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
-        let fixt = Fixture::new(Some("two-started-entries.csv"))?;
+        let fixt = Fixture::new(Some("started-with-label.csv"))?;
         assert_eq!(
             Some((true, 83)),
-            super::_stop_current_entry(fixt.t_data_file(), &ts)?
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
+        );
+        assert_eq!(
+            "2020-08-07 14:00 -0400,2020-08-07 15:23 -0400,acme\n",
+            fixt.read()?
         );
         Ok(())
     }
 
     #[test]
-    fn test_read_last_entry_no_file() -> TestRes {
-        let fixt = Fixture::new(None)?;
-        assert_eq!(None, fixt.open()?.read_last_entry(&DefaultTimeSource)?);
+    fn test_stop_at() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(18:00), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 07), time!(17:40), offset!(-04:00)).now();
+        let fixt = Fixture::new(Some("started-with-comma.csv"))?;
+        assert_eq!(
+            Some((true, 220)),
+            super::_stop_current_entry(fixt.t_data_file(), None, Some(at), &ts)?
+        );
+        assert_eq!(
+            "2020-08-07 14:00 -0400,2020-08-07 17:40 -0400\n",
+            fixt.read()?
+        );
         Ok(())
     }
 
     #[test]
-    fn test_read_last_entry_empty_file() -> TestRes {
-        let fixt = Fixture::new(Some("empty.csv"))?;
-        assert_eq!(None, fixt.open()?.read_last_entry(&DefaultTimeSource)?);
+    fn test_stop_at_before_start() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(18:00), offset!(-04:00));
+        let at = mock_time(date!(2020 - 08 - 07), time!(13:40), offset!(-04:00)).now();
+        let fixt = Fixture::new(Some("started-with-comma.csv"))?;
+        assert!(super::_stop_current_entry(fixt.t_data_file(), None, Some(at), &ts).is_err());
+        assert_eq!("2020-08-07 14:00 -0400,\n", fixt.read()?);
         Ok(())
     }
 
     #[test]
-    fn test_read_last_entry() -> TestRes {
-        let fixt = Fixture::new(Some("three-entries.csv"))?;
-        match fixt.open()?.read_last_entry(&DefaultTimeSource)? {
-            None => panic!("expected an entry"),
-            Some(e) => assert_eq!(
-                "2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n",
-                format!("{}", e)
-            ),
-        };
+    fn test_stop_with_note() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("started-with-note.csv"))?;
+        assert_eq!(
+            Some((true, 83)),
+            super::_stop_current_entry(fixt.t_data_file(), Some("second".to_string()), None, &ts)?
+        );
+        assert_eq!(
+            "2020-08-06 14:00 -0400,2020-08-06 17:55 -0400\n\
+             2020-08-07 14:00 -0400,2020-08-07 15:23 -0400,acme\n\
+             \x20 # a note long enough to push the entry past the first guess of where to look\n\
+             \x20 # second\n",
+            fixt.read()?
+        );
         Ok(())
     }
 
     #[test]
-    fn test_read_last_entries_no_file() -> TestRes {
-        let fixt = Fixture::new(None)?;
+    fn test_stop_finished_entry_with_note() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(23:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
         assert_eq!(
-            empty_entries(),
-            fixt.open()?.read_last_entries(10, &DefaultTimeSource)?
+            Some((false, 328)),
+            super::_stop_current_entry(fixt.t_data_file(), Some("done".to_string()), None, &ts)?
+        );
+        assert_eq!(
+            "2020-08-06 15:38 -0400,2020-08-06 18:40 -0400\n\
+             2020-08-07 11:03 -0400,2020-08-07 13:07 -0400\n\
+             2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n\
+             \x20 # done\n",
+            fixt.read()?
         );
         Ok(())
     }
 
     #[test]
-    fn test_read_last_entries_empty_file() -> TestRes {
-        let fixt = Fixture::new(Some("empty.csv"))?;
+    fn test_add_note() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("started-with-note.csv"))?;
+        let entry = super::_add_note(fixt.t_data_file(), "second".to_string(), &ts)?;
+        assert_eq!(2, entry.map(|e| e.notes.len()).unwrap_or(0));
         assert_eq!(
-            empty_entries(),
-            fixt.open()?.read_last_entries(10, &DefaultTimeSource)?
+            "2020-08-06 14:00 -0400,2020-08-06 17:55 -0400\n\
+             2020-08-07 14:00 -0400,,acme\n\
+             \x20 # a note long enough to push the entry past the first guess of where to look\n\
+             \x20 # second\n",
+            fixt.read()?
         );
         Ok(())
     }
 
     #[test]
-    fn test_read_last_entries() -> TestRes {
-        let fixt = Fixture::new(Some("thousand-entries.csv"))?;
-        assert!(
-            fixt.open()?
-                .read_last_entries(10, &DefaultTimeSource)?
-                .len()
-                >= 10,
-            "expect at least 10 entries to be returned"
-        );
-        assert!(
-            fixt.open()?
-                .read_last_entries(100, &DefaultTimeSource)?
-                .len()
-                >= 100,
-            "expect at least 100 entries to be returned"
-        );
-        // the file only has 1000 entries, so we can't get more than that.
+    fn test_add_note_in_new_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
         assert_eq!(
-            1000,
-            fixt.open()?
-                .read_last_entries(1000, &DefaultTimeSource)?
-                .len()
+            None,
+            super::_add_note(fixt.t_data_file(), "note".to_string(), &DefaultTimeSource)?
         );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_when_two_entries_are_pending() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("two-started-entries.csv"))?;
         assert_eq!(
-            1000,
-            fixt.open()?
-                .read_last_entries(10000, &DefaultTimeSource)?
-                .len()
+            Some((true, 83)),
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
         );
         Ok(())
     }
 
     #[test]
-    fn test_read_entries_no_file() -> TestRes {
+    fn test_stop_when_note_follows_pending_entry() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
         let fixt = Fixture::new(None)?;
-        assert_eq!(
-            empty_entries(),
-            fixt.open()?.read_entries(&DefaultTimeSource)?
-        );
+        let contents = "2020-08-07 14:00 -0400\n# a free note\n";
+        std::fs::write(fixt.t_data_file(), contents)?;
+        assert!(super::_stop_current_entry(fixt.t_data_file(), None, None, &ts).is_err());
+        assert!(super::_add_note(fixt.t_data_file(), "note".to_string(), &ts).is_err());
+        assert_eq!(contents, fixt.read()?);
         Ok(())
     }
 
     #[test]
-    fn test_read_entries_empty_file() -> TestRes {
-        let fixt = Fixture::new(Some("empty.csv"))?;
+    fn test_import_notes_before_pending_entry() -> TestRes {
+        let ts = mock_time(date!(2020 - 08 - 07), time!(15:23), offset!(-04:00));
+        let fixt = Fixture::new(Some("started-with-comma.csv"))?;
+        let note = Entry::Note(" imported".to_string());
+        super::_import_entries(fixt.t_data_file(), vec![note], &ts)?;
+        assert_eq!("# imported\n2020-08-07 14:00 -0400\n", fixt.read()?);
         assert_eq!(
-            empty_entries(),
-            fixt.open()?.read_entries(&DefaultTimeSource)?
+            Some((true, 83)),
+            super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?
         );
         Ok(())
     }
 
+    fn finished_entry(
+        start: (time::Date, time::Time),
+        stop: (time::Date, time::Time),
+    ) -> super::TimeEntry {
+        let start = mock_time(start.0, start.1, offset!(-04:00)).now();
+        let stop = mock_time(stop.0, stop.1, offset!(-04:00)).now();
+        super::TimeEntry::start_at(start).finish_at(stop)
+    }
+
     #[test]
-    fn test_read_entries_blank_file() -> TestRes {
-        let fixt = Fixture::new(Some("blank-lines.csv"))?;
+    fn test_insert_in_new_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let entry = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:10)),
+            (date!(2020 - 08 - 07), time!(13:50)),
+        );
+        super::_insert_entry(fixt.t_data_file(), entry, &DefaultTimeSource)?;
         assert_eq!(
-            empty_entries(),
-            fixt.open()?.read_entries(&DefaultTimeSource)?
+            "2020-08-07 13:10 -0400,2020-08-07 13:50 -0400\n",
+            fixt.read()?
         );
         Ok(())
     }
 
     #[test]
-    fn test_read_entries_three() -> TestRes {
+    fn test_insert_between_entries() -> TestRes {
         let fixt = Fixture::new(Some("three-entries.csv"))?;
-        assert_eq!(3, fixt.open()?.read_entries(&DefaultTimeSource)?.len());
+        let entry = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:07)),
+            (date!(2020 - 08 - 07), time!(14:00)),
+        );
+        super::_insert_entry(fixt.t_data_file(), entry, &DefaultTimeSource)?;
+        assert_eq!(
+            "2020-08-06 15:38 -0400,2020-08-06 18:40 -0400\n\
+             2020-08-07 11:03 -0400,2020-08-07 13:07 -0400\n\
+             2020-08-07 13:07 -0400,2020-08-07 14:00 -0400\n\
+             2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n",
+            fixt.read()?
+        );
         Ok(())
     }
 
     #[test]
-    fn test_read_entries_thousand() -> TestRes {
-        let fixt = Fixture::new(Some("thousand-entries.csv"))?;
-        assert_eq!(1000, fixt.open()?.read_entries(&DefaultTimeSource)?.len());
+    fn test_import_entries() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let late = finished_entry(
+            (date!(2020 - 08 - 08), time!(09:00)),
+            (date!(2020 - 08 - 08), time!(10:00)),
+        );
+        let overlapping = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:00)),
+            (date!(2020 - 08 - 07), time!(13:30)),
+        );
+        let between = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:07)),
+            (date!(2020 - 08 - 07), time!(14:00)),
+        )
+        .with_label(Some("acme".to_string()))
+        .with_note(Some("from toggl".to_string()));
+        let summary = super::_import_entries(
+            fixt.t_data_file(),
+            vec![
+                late.into(),
+                Entry::Note(" about the 8th".to_string()),
+                overlapping.clone().into(),
+                Entry::Note(" dropped with the overlapping entry".to_string()),
+                between.into(),
+            ],
+            &DefaultTimeSource,
+        )?;
+        assert_eq!(3, summary.added);
+        assert_eq!(
+            vec![(
+                overlapping,
+                "2020-08-07 13:00 -0400 starts before previous entry stops 2020-08-07 13:07 -0400"
+                    .to_string()
+            )],
+            summary.rejected
+        );
+        assert_eq!(
+            "2020-08-06 15:38 -0400,2020-08-06 18:40 -0400\n\
+             2020-08-07 11:03 -0400,2020-08-07 13:07 -0400\n\
+             2020-08-07 13:07 -0400,2020-08-07 14:00 -0400,acme\n  # from toggl\n\
+             2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n\
+             2020-08-08 09:00 -0400,2020-08-08 10:00 -0400\n\
+             # about the 8th\n",
+            fixt.read()?
+        );
+
+        // The whole import is undone at once.
+        super::_undo_change(fixt.t_data_file(), &DefaultTimeSource)?;
+        assert_eq!(original, fixt.read()?);
         Ok(())
     }
 
     #[test]
-    fn test_read_entries_started() -> TestRes {
-        let fixt = Fixture::new(Some("started-with-comma.csv"))?;
-        let entries = fixt.open()?.read_entries(&DefaultTimeSource)?;
+    fn test_import_notes_with_line_breaks() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let entry = finished_entry(
+            (date!(2020 - 08 - 08), time!(09:00)),
+            (date!(2020 - 08 - 08), time!(10:00)),
+        )
+        .with_note(Some("line1\n2020-08-07 12:00,2020-08-07 13:00".to_string()));
+        super::_import_entries(
+            fixt.t_data_file(),
+            vec![entry.into(), Entry::Note(" hi\r\n  there\n".to_string())],
+            &DefaultTimeSource,
+        )?;
+        assert_eq!(
+            format!(
+                "{}2020-08-08 09:00 -0400,2020-08-08 10:00 -0400\n\
+                 \x20 # line1 2020-08-07 12:00,2020-08-07 13:00\n\
+                 # hi there\n",
+                original
+            ),
+            fixt.read()?
+        );
+        let entries = super::open_unlocked(fixt.t_data_file())?.read_entries(&DefaultTimeSource)?;
+        assert_eq!(5, entries.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_first_and_last() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let first = finished_entry(
+            (date!(2020 - 08 - 05), time!(09:00)),
+            (date!(2020 - 08 - 05), time!(10:00)),
+        );
+        let last = finished_entry(
+            (date!(2020 - 08 - 08), time!(09:00)),
+            (date!(2020 - 08 - 08), time!(10:00)),
+        );
+        super::_insert_entry(fixt.t_data_file(), first, &DefaultTimeSource)?;
+        super::_insert_entry(fixt.t_data_file(), last, &DefaultTimeSource)?;
+        assert_eq!(
+            "2020-08-05 09:00 -0400,2020-08-05 10:00 -0400\n\
+             2020-08-06 15:38 -0400,2020-08-06 18:40 -0400\n\
+             2020-08-07 11:03 -0400,2020-08-07 13:07 -0400\n\
+             2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n\
+             2020-08-08 09:00 -0400,2020-08-08 10:00 -0400\n",
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_overlapping() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let before = fixt.read()?;
+        let overlaps_prev = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:00)),
+            (date!(2020 - 08 - 07), time!(13:30)),
+        );
+        let overlaps_next = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:30)),
+            (date!(2020 - 08 - 07), time!(14:01)),
+        );
+        let backwards = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:50)),
+            (date!(2020 - 08 - 07), time!(13:30)),
+        );
+        for entry in [overlaps_prev, overlaps_next, backwards] {
+            assert!(super::_insert_entry(fixt.t_data_file(), entry, &DefaultTimeSource).is_err());
+        }
+        assert_eq!(before, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_after_pending_entry() -> TestRes {
+        let fixt = Fixture::new(Some("started-with-comma.csv"))?;
+        let entry = finished_entry(
+            (date!(2020 - 08 - 07), time!(15:00)),
+            (date!(2020 - 08 - 07), time!(15:30)),
+        );
+        assert!(super::_insert_entry(fixt.t_data_file(), entry, &DefaultTimeSource).is_err());
+        Ok(())
+    }
+
+    fn undone(summary: Option<super::ChangeSummary>) -> (Vec<String>, Vec<String>) {
+        let summary = summary.expect("expected a change");
+        let fmt = |entries: Vec<Entry>| entries.iter().map(|e| format!("{}", e)).collect();
+        (fmt(summary.restored), fmt(summary.removed))
+    }
+
+    #[test]
+    fn test_undo_nothing() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        assert!(super::_undo_change(fixt.t_data_file(), &DefaultTimeSource)?.is_none());
+        assert!(super::_redo_change(fixt.t_data_file(), &DefaultTimeSource)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_and_redo_start_and_stop() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?;
+        let started = fixt.read()?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(11:00), offset!(-04:00));
+        super::_stop_current_entry(fixt.t_data_file(), None, None, &ts)?;
+        let stopped = fixt.read()?;
+
+        assert_eq!(
+            (
+                vec!["2020-08-08 10:23 -0400\n".to_string()],
+                vec!["2020-08-08 10:23 -0400,2020-08-08 11:00 -0400\n".to_string()]
+            ),
+            undone(super::_undo_change(fixt.t_data_file(), &ts)?)
+        );
+        assert_eq!(started, fixt.read()?);
+        assert_eq!(
+            (vec![], vec!["2020-08-08 10:23 -0400\n".to_string()]),
+            undone(super::_undo_change(fixt.t_data_file(), &ts)?)
+        );
+        assert_eq!(original, fixt.read()?);
+        assert!(super::_undo_change(fixt.t_data_file(), &ts)?.is_none());
+
+        assert_eq!(
+            (vec!["2020-08-08 10:23 -0400\n".to_string()], vec![]),
+            undone(super::_redo_change(fixt.t_data_file(), &ts)?)
+        );
+        assert_eq!(started, fixt.read()?);
+        super::_redo_change(fixt.t_data_file(), &ts)?;
+        assert_eq!(stopped, fixt.read()?);
+        assert!(super::_redo_change(fixt.t_data_file(), &ts)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_with_truncated_journal() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let journal = fixt.dir.path().join("test-t.csv.journal");
+        std::fs::write(&journal, "t journal 1\napplied 0 0 100\n2020")?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?;
+        assert!(super::_undo_change(fixt.t_data_file(), &ts)?.is_some());
+        assert_eq!(original, fixt.read()?);
+        assert!(super::_undo_change(fixt.t_data_file(), &ts)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_replace() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?;
+        let uploaded = "2020-08-08 09:00 -0400,2020-08-08 10:00 -0400\n";
+        super::replace_data_file(fixt.t_data_file(), uploaded.as_bytes())?;
+        super::_undo_change(fixt.t_data_file(), &ts)?;
+        assert_eq!(original.clone() + "2020-08-08 10:23 -0400\n", fixt.read()?);
+        super::_undo_change(fixt.t_data_file(), &ts)?;
+        assert_eq!(original, fixt.read()?);
+        super::_redo_change(fixt.t_data_file(), &ts)?;
+        super::_redo_change(fixt.t_data_file(), &ts)?;
+        assert_eq!(uploaded, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_insert() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let entry = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:07)),
+            (date!(2020 - 08 - 07), time!(14:00)),
+        );
+        super::_insert_entry(fixt.t_data_file(), entry, &DefaultTimeSource)?;
+        assert_eq!(
+            (
+                vec![],
+                vec!["2020-08-07 13:07 -0400,2020-08-07 14:00 -0400\n".to_string()]
+            ),
+            undone(super::_undo_change(fixt.t_data_file(), &DefaultTimeSource)?)
+        );
+        assert_eq!(original, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_backup() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let original = fixt.read()?;
+        let entry = finished_entry(
+            (date!(2020 - 08 - 07), time!(13:07)),
+            (date!(2020 - 08 - 07), time!(14:00)),
+        );
+        super::_insert_entry(fixt.t_data_file(), entry, &DefaultTimeSource)?;
+        let inserted = fixt.read()?;
+
+        let backups = crate::backup::list(fixt.t_data_file())?;
+        assert_eq!(1, backups.len());
+        super::_restore_backup(fixt.t_data_file(), &backups[0])?;
+        assert_eq!(original, fixt.read()?);
+
+        super::_undo_change(fixt.t_data_file(), &DefaultTimeSource)?;
+        assert_eq!(inserted, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_file() -> TestRes {
+        let fixt = Fixture::new(Some("unformatted.csv"))?;
+        let ts = mock_time(date!(2020 - 07 - 16), time!(10:00), offset!(-04:00));
+        let original = fixt.read()?;
+        let expected = super::FormatSummary {
+            changed: 3,
+            blank: 2,
+        };
+        assert_eq!(
+            expected,
+            super::_format_file(fixt.t_data_file(), false, true, &ts)?
+        );
+        assert_eq!(original, fixt.read()?);
+
+        assert_eq!(
+            expected,
+            super::_format_file(fixt.t_data_file(), false, false, &ts)?
+        );
+        assert_eq!(
+            "2020-07-15 09:00 -0400,2020-07-15 10:00,email\n  \
+             # read the inbox\n\
+             # planning\n\
+             2020-07-15 10:30 -0400,2020-07-15 12:00 -0400,code\n\
+             2020-07-15 13:00,2020-07-15 14:00 -0400,code,resume\n",
+            fixt.read()?
+        );
+        assert!(super::_format_file(fixt.t_data_file(), false, true, &ts)?.is_empty());
+
+        super::_undo_change(fixt.t_data_file(), &ts)?;
+        assert_eq!(original, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_file_explicit_tz() -> TestRes {
+        let fixt = Fixture::new(Some("unformatted.csv"))?;
+        let ts = mock_time(date!(2020 - 07 - 16), time!(10:00), offset!(-04:00));
+        let summary = super::_format_file(fixt.t_data_file(), true, false, &ts)?;
+        assert_eq!(4, summary.changed);
+        assert_eq!(
+            "2020-07-15 09:00 -0400,2020-07-15 10:00 -0400,email\n  \
+             # read the inbox\n\
+             # planning\n\
+             2020-07-15 10:30 -0400,2020-07-15 12:00 -0400,code\n\
+             2020-07-15 13:00 -0400,2020-07-15 14:00 -0400,code,resume\n",
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_file_with_syntax_error() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        std::fs::write(
+            fixt.t_data_file(),
+            "2020-07-15 09:00 -0400,\n\n2020-07-15 10\n",
+        )?;
+        let err = super::_format_file(fixt.t_data_file(), false, false, &DefaultTimeSource)
+            .expect_err("the file can't be parsed");
+        assert!(err.to_string().contains("line 3"));
+        assert_eq!("2020-07-15 09:00 -0400,\n\n2020-07-15 10\n", fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2020 - 07 - 16), time!(10:00), offset!(-04:00));
+        let original = "2020-07-15 09:00 -0400\n\n2020-07-15 10:00 -0400,2020-07-15 11:00 -0400\n";
+        std::fs::write(fixt.t_data_file(), original)?;
+
+        let repairs = super::_repair_file(fixt.t_data_file(), |_| false, &ts)?;
+        assert_eq!(1, repairs.declined.len());
+        assert_eq!(original, fixt.read()?);
+
+        let repairs = super::_repair_file(fixt.t_data_file(), |_| true, &ts)?;
+        assert_eq!(1, repairs.applied.len());
+        assert_eq!(
+            "2020-07-15 09:00 -0400,2020-07-15 10:00 -0400\n\
+             \n\
+             2020-07-15 10:00 -0400,2020-07-15 11:00 -0400\n",
+            fixt.read()?
+        );
+
+        super::_undo_change(fixt.t_data_file(), &ts)?;
+        assert_eq!(original, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_file_keeps_the_rest() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2020 - 07 - 16), time!(10:00), offset!(-04:00));
+        std::fs::write(
+            fixt.t_data_file(),
+            "# spacing   and all\n\
+             2020-07-15 08:00 -0400,  2020-07-15 08:30 -0400\n\
+             \x20   # an indented note\n\
+             \n\
+             2020-07-15 09:00 -0400,,acme\n\
+             \x20 # the note that goes with it\n\
+             2020-07-15 10:00 -0400,2020-07-15 11:00 -0400\n",
+        )?;
+        super::_repair_file(fixt.t_data_file(), |_| true, &ts)?;
+        assert_eq!(
+            "# spacing   and all\n\
+             2020-07-15 08:00 -0400,  2020-07-15 08:30 -0400\n\
+             \x20   # an indented note\n\
+             \n\
+             2020-07-15 09:00 -0400,2020-07-15 10:00 -0400,acme\n\
+             \x20 # the note that goes with it\n\
+             2020-07-15 10:00 -0400,2020-07-15 11:00 -0400\n",
+            fixt.read()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_file_changed_meanwhile() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2020 - 07 - 16), time!(10:00), offset!(-04:00));
+        std::fs::write(
+            fixt.t_data_file(),
+            "2020-07-15 09:00 -0400\n2020-07-15 10:00 -0400\n",
+        )?;
+        let path = fixt.t_data_file();
+        let result = super::_repair_file(
+            fixt.t_data_file(),
+            |_| std::fs::write(&path, "2020-07-15 09:00 -0400\n").is_ok(),
+            &ts,
+        );
+        assert!(result.is_err());
+        assert_eq!("2020-07-15 09:00 -0400\n", fixt.read()?);
+        Ok(())
+    }
+
+    const HISTORY: &str = "# from before the archives\n\
+                           2021-12-31 22:00 -0500,2022-01-01 01:00 -0500\n\
+                           2022-06-01 09:00 -0400,2022-06-01 10:00 -0400\n\
+                           # the end of 2022\n\
+                           2023-01-02 09:00 -0500,2023-01-02 10:00 -0500\n\
+                           2023-01-03 09:00 -0500\n";
+
+    #[test]
+    fn test_archive_entries() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2023 - 01 - 03), time!(12:00), offset!(-05:00));
+        std::fs::write(fixt.t_data_file(), HISTORY)?;
+        let before = fixt.open()?.read_entries(&ts)?;
+
+        let archived = super::_archive_entries(fixt.t_data_file(), 2023, &ts)?;
+        let archive = |year| fixt.dir.path().join(format!("test-t-{}.csv", year));
+        assert_eq!(vec![(archive(2021), 1), (archive(2022), 1)], archived);
+        assert_eq!(
+            "# from before the archives\n2021-12-31 22:00 -0500,2022-01-01 01:00 -0500\n",
+            std::fs::read_to_string(archive(2021))?
+        );
+        assert_eq!(
+            "2022-06-01 09:00 -0400,2022-06-01 10:00 -0400\n# the end of 2022\n",
+            std::fs::read_to_string(archive(2022))?
+        );
+        assert_eq!(
+            "2023-01-02 09:00 -0500,2023-01-02 10:00 -0500\n2023-01-03 09:00 -0500\n",
+            fixt.read()?
+        );
+        assert_eq!(before, fixt.open()?.read_entries(&ts)?);
+        // The journal is cleared, since undoing would bring back archived entries.
+        assert!(super::_undo_change(fixt.t_data_file(), &ts)?.is_none());
+
+        assert!(super::_archive_entries(fixt.t_data_file(), 2023, &ts)?.is_empty());
+        let entry = finished_entry(
+            (date!(2022 - 07 - 01), time!(09:00)),
+            (date!(2022 - 07 - 01), time!(10:00)),
+        );
+        assert!(super::_insert_entry(fixt.t_data_file(), entry, &ts).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_behind_newer_archive() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2023 - 01 - 03), time!(12:00), offset!(-05:00));
+        std::fs::write(fixt.t_data_file(), HISTORY)?;
+        std::fs::write(fixt.dir.path().join("test-t-2022.csv"), "")?;
+        let result = super::_archive_entries(fixt.t_data_file(), 2023, &ts);
+        assert!(result.is_err());
+        assert_eq!(HISTORY, fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_archives() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2023 - 01 - 03), time!(12:00), offset!(-05:00));
+        std::fs::write(fixt.t_data_file(), HISTORY)?;
+        let before = fixt.open()?;
+        let (last, since) = (
+            before.read_last_entries(4, &ts)?,
+            fixt.open()?.read_entries_since(datetime(2022, 3, 1), &ts)?,
+        );
+        super::_archive_entries(fixt.t_data_file(), 2023, &ts)?;
+
+        assert_eq!(last, fixt.open()?.read_last_entries(4, &ts)?);
+        assert_eq!(
+            since,
+            fixt.open()?.read_entries_since(datetime(2022, 3, 1), &ts)?
+        );
+        // Only the data file is read when it goes back far enough.
+        assert_eq!(
+            2,
+            fixt.open()?
+                .read_entries_since(datetime(2023, 1, 3), &ts)?
+                .len()
+        );
+        assert_eq!(6, fixt.open()?.read_entries_recovering(&ts)?.entries.len());
+        Ok(())
+    }
+
+    fn datetime(year: i32, month: u8, day: u8) -> time::OffsetDateTime {
+        time::Date::try_from_ymd(year, month, day)
+            .unwrap()
+            .midnight()
+            .assume_offset(offset!(-05:00))
+    }
+
+    #[test]
+    fn test_undo_after_file_changed() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?;
+        std::fs::write(fixt.t_data_file(), "2020-08-08 10:00 -0400\n")?;
+        assert!(super::_undo_change(fixt.t_data_file(), &ts).is_err());
+        assert_eq!("2020-08-08 10:00 -0400\n", fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_change_clears_redo() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        let ts = mock_time(date!(2020 - 08 - 08), time!(10:23), offset!(-04:00));
+        super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?;
+        super::_undo_change(fixt.t_data_file(), &ts)?;
+        super::_add_note(fixt.t_data_file(), "note".to_string(), &ts)?;
+        super::_start_new_entry(fixt.t_data_file(), None, None, &ts)?;
+        super::_undo_change(fixt.t_data_file(), &ts)?;
+        super::_start_new_entry(fixt.t_data_file(), Some("x".to_string()), None, &ts)?;
+        assert!(super::_redo_change(fixt.t_data_file(), &ts)?.is_none());
+        assert_eq!("2020-08-08 10:23 -0400,,x\n", fixt.read()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entry_no_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        assert_eq!(None, fixt.open()?.read_last_entry(&DefaultTimeSource)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entry_empty_file() -> TestRes {
+        let fixt = Fixture::new(Some("empty.csv"))?;
+        assert_eq!(None, fixt.open()?.read_last_entry(&DefaultTimeSource)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entry() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        match fixt.open()?.read_last_entry(&DefaultTimeSource)? {
+            None => panic!("expected an entry"),
+            Some(e) => assert_eq!(
+                "2020-08-07 14:00 -0400,2020-08-07 17:55 -0400\n",
+                format!("{}", e)
+            ),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entries_no_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        assert_eq!(
+            empty_entries(),
+            fixt.open()?.read_last_entries(10, &DefaultTimeSource)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entries_empty_file() -> TestRes {
+        let fixt = Fixture::new(Some("empty.csv"))?;
+        assert_eq!(
+            empty_entries(),
+            fixt.open()?.read_last_entries(10, &DefaultTimeSource)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entries() -> TestRes {
+        let fixt = Fixture::new(Some("thousand-entries.csv"))?;
+        assert_eq!(
+            10,
+            fixt.open()?
+                .read_last_entries(10, &DefaultTimeSource)?
+                .len()
+        );
+        assert_eq!(
+            100,
+            fixt.open()?
+                .read_last_entries(100, &DefaultTimeSource)?
+                .len()
+        );
+        // the file only has 1000 entries, so we can't get more than that.
+        assert_eq!(
+            1000,
+            fixt.open()?
+                .read_last_entries(1000, &DefaultTimeSource)?
+                .len()
+        );
+        assert_eq!(
+            1000,
+            fixt.open()?
+                .read_last_entries(10000, &DefaultTimeSource)?
+                .len()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_last_entries_with_long_notes() -> TestRes {
+        let fixt = Fixture::new(Some("long-notes.csv"))?;
+        let all = fixt.open()?.read_entries(&DefaultTimeSource)?;
+        assert_eq!(8, all.len());
+        for n in 1..=10 {
+            let expected = &all[all.len().saturating_sub(n)..];
+            let actual = fixt
+                .open()?
+                .read_last_entries(n as u64, &DefaultTimeSource)?;
+            assert_eq!(expected, actual.as_slice(), "last {} entries", n);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_since() -> TestRes {
+        let fixt = Fixture::new(Some("long-notes.csv"))?;
+        let all = fixt.open()?.read_entries(&DefaultTimeSource)?;
+        let since = |date: time::Date, t: time::Time| -> Result<Vec<Entry>, Box<dyn Error>> {
+            let since = date.with_time(t).assume_offset(offset!(-04:00));
+            Ok(fixt.open()?.read_entries_since(since, &DefaultTimeSource)?)
+        };
+        assert_eq!(all[6..], since(date!(2020 - 08 - 06), time!(00:00))?[..]);
+        assert_eq!(all[5..], since(date!(2020 - 08 - 04), time!(10:00))?[..]);
+        assert_eq!(all[7..], since(date!(2020 - 08 - 07), time!(00:00))?[..]);
+        assert_eq!(all, since(date!(2020 - 08 - 01), time!(00:00))?);
+        assert_eq!(
+            empty_entries(),
+            Fixture::new(None)?.open()?.read_entries_since(
+                date!(2020 - 08 - 01).midnight().assume_utc(),
+                &DefaultTimeSource
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_no_file() -> TestRes {
+        let fixt = Fixture::new(None)?;
+        assert_eq!(
+            empty_entries(),
+            fixt.open()?.read_entries(&DefaultTimeSource)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_empty_file() -> TestRes {
+        let fixt = Fixture::new(Some("empty.csv"))?;
+        assert_eq!(
+            empty_entries(),
+            fixt.open()?.read_entries(&DefaultTimeSource)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_blank_file() -> TestRes {
+        let fixt = Fixture::new(Some("blank-lines.csv"))?;
+        assert_eq!(
+            empty_entries(),
+            fixt.open()?.read_entries(&DefaultTimeSource)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_three() -> TestRes {
+        let fixt = Fixture::new(Some("three-entries.csv"))?;
+        assert_eq!(3, fixt.open()?.read_entries(&DefaultTimeSource)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_thousand() -> TestRes {
+        let fixt = Fixture::new(Some("thousand-entries.csv"))?;
+        assert_eq!(1000, fixt.open()?.read_entries(&DefaultTimeSource)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entries_started() -> TestRes {
+        let fixt = Fixture::new(Some("started-with-comma.csv"))?;
+        let entries = fixt.open()?.read_entries(&DefaultTimeSource)?;
         assert_eq!(1, entries.len());
         assert!(!entries[0].time().is_finished());
         Ok(())
     }
 }
 
-pub fn t_data_file() -> Result<String, &'static str> {
-    std::env::var("T_DATA_FILE").or(Err("T_DATA_FILE must be set"))
+pub fn t_data_file() -> Result<String, Box<dyn Error>> {
+    let path = resolve_links(Config::current()?.data_file()?);
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// If the data file is a symlink, the files that go with it, like the lock and the backups, go
+// with the file that it points to, and that's the file that gets replaced when it changes.
+fn resolve_links(mut path: PathBuf) -> PathBuf {
+    // The same limit as Linux has on following links, in case they go around in a circle.
+    for _ in 0..40 {
+        match std::fs::read_link(&path) {
+            Ok(target) => path = path.with_file_name("").join(target),
+            Err(_) => break,
+        }
+    }
+    path
+}
+
+// If there isn't a pending entry, start a new one. The entry starts now unless another time is
+// given.
+pub fn start_new_entry<TS: TimeSource>(
+    label: Option<String>,
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<Option<i64>, Box<dyn Error>> {
+    _start_new_entry(t_data_file()?, label, at, ts)
+}
+
+fn _start_new_entry<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    label: Option<String>,
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<Option<i64>, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let mut tail = read_for_update(&t_data_file, ts)?;
+    if let Some(entry) = &tail.last {
+        if !entry.is_finished() {
+            return Ok(Some(entry.minutes(ts)));
+        }
+    }
+    check_label(&label)?;
+    let new_entry = TimeEntry::start_at(check_not_future(at, ts)?).with_label(label);
+    new_entry.is_valid_after(&tail.last)?;
+    replace_tail(t_data_file, &mut tail.f, tail.stop_pos, new_entry)?;
+    Ok(None)
 }
 
-// If there isn't a pending entry, start a new one.
-pub fn start_new_entry<TS: TimeSource>(ts: &TS) -> Result<Option<i64>, Box<dyn Error>> {
-    _start_new_entry(t_data_file()?, ts)
+// Stop the pending entry and start a new one with the given label at the same moment, in a single
+// update of the file. If nothing is pending, this only starts the new entry. Returns the minutes
+// worked on the entry that was stopped.
+pub fn switch_entry<TS: TimeSource>(
+    label: Option<String>,
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<Option<i64>, Box<dyn Error>> {
+    _switch_entry(t_data_file()?, label, at, ts)
 }
 
-fn _start_new_entry<P: AsRef<Path>, TS: TimeSource>(
+fn _switch_entry<P: AsRef<Path>, TS: TimeSource>(
     t_data_file: P,
+    label: Option<String>,
+    at: Option<OffsetDateTime>,
     ts: &TS,
 ) -> Result<Option<i64>, Box<dyn Error>> {
-    let (mut f, entry, _, pos) = read_for_update(t_data_file, ts)?;
-    if let Some(entry) = entry {
-        if !entry.is_finished() {
-            return Ok(Some(entry.minutes(ts)));
+    let _lock = lock_exclusive(&t_data_file)?;
+    check_label(&label)?;
+    let at = check_not_future(at, ts)?;
+    let mut tail = read_for_update(&t_data_file, ts)?;
+    let new_entry = TimeEntry::start_at(at).with_label(label);
+    match tail.last {
+        Some(entry) if !entry.is_finished() => {
+            let entry = entry.finish_at(at);
+            entry.is_valid_after(&None)?;
+            let minutes = entry.minutes(ts);
+            let new_tail = format!("{}{}", entry, new_entry);
+            replace_tail(t_data_file, &mut tail.f, tail.start_pos, new_tail)?;
+            Ok(Some(minutes))
+        }
+        entry => {
+            new_entry.is_valid_after(&entry)?;
+            replace_tail(t_data_file, &mut tail.f, tail.stop_pos, new_entry)?;
+            Ok(None)
         }
     }
-    f.seek(SeekFrom::Start(pos))?;
-    write!(f, "{}", TimeEntry::start(ts))?;
+}
+
+// Start a new entry that continues the session of the last entry, after a pause. The new entry gets
+// the same label as the last one.
+pub fn resume_last_entry<TS: TimeSource>(
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<Option<i64>, Box<dyn Error>> {
+    _resume_last_entry(t_data_file()?, at, ts)
+}
+
+fn _resume_last_entry<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<Option<i64>, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let mut tail = read_for_update(&t_data_file, ts)?;
+    let label = match &tail.last {
+        None => return Err("there isn't an entry to resume".into()),
+        Some(entry) if !entry.is_finished() => return Ok(Some(entry.minutes(ts))),
+        Some(entry) => entry.label.clone(),
+    };
+    let new_entry = TimeEntry::start_at(check_not_future(at, ts)?)
+        .with_label(label)
+        .resuming();
+    new_entry.is_valid_after(&tail.last)?;
+    replace_tail(t_data_file, &mut tail.f, tail.stop_pos, new_entry)?;
     Ok(None)
 }
 
-// If there is a pending entry, finish it.
-pub fn stop_current_entry<TS: TimeSource>(ts: &TS) -> Result<Option<(bool, i64)>, Box<dyn Error>> {
-    _stop_current_entry(t_data_file()?, ts)
+// If there is a pending entry, finish it. The note, if there is one, is attached to the entry
+// whether or not it was still pending.
+pub fn stop_current_entry<TS: TimeSource>(
+    note: Option<String>,
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<Option<(bool, i64)>, Box<dyn Error>> {
+    _stop_current_entry(t_data_file()?, note, at, ts)
 }
 
 fn _stop_current_entry<P: AsRef<Path>, TS: TimeSource>(
     t_data_file: P,
+    note: Option<String>,
+    at: Option<OffsetDateTime>,
     ts: &TS,
 ) -> Result<Option<(bool, i64)>, Box<dyn Error>> {
-    let (mut f, entry, pos, _) = read_for_update(t_data_file, ts)?;
-    match entry {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let mut tail = read_for_update(&t_data_file, ts)?;
+    let (f, pos, at_end) = (&mut tail.f, tail.start_pos, tail.at_end);
+    match tail.last.filter(|_| at_end) {
         None => Ok(None),
         Some(entry) => {
             if entry.is_finished() {
-                Ok(Some((false, entry.minutes_since_stop(ts).unwrap())))
+                let minutes = entry.minutes_since_stop(ts).unwrap();
+                if note.is_some() {
+                    replace_tail(t_data_file, f, pos, entry.with_note(note))?;
+                }
+                Ok(Some((false, minutes)))
             } else {
-                f.seek(SeekFrom::Start(pos))?;
-                let entry = entry.finish(ts);
-                write!(f, "{}", entry)?;
-                Ok(Some((true, entry.minutes(ts))))
+                let entry = entry.finish_at(check_not_future(at, ts)?).with_note(note);
+                entry.is_valid_after(&None)?;
+                let minutes = entry.minutes(ts);
+                replace_tail(t_data_file, f, pos, entry)?;
+                Ok(Some((true, minutes)))
+            }
+        }
+    }
+}
+
+// Attach a note to the current or last entry. Returns the updated entry, or None if there isn't a
+// time entry at the end of the file.
+pub fn add_note<TS: TimeSource>(
+    note: String,
+    ts: &TS,
+) -> Result<Option<TimeEntry>, Box<dyn Error>> {
+    _add_note(t_data_file()?, note, ts)
+}
+
+fn _add_note<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    note: String,
+    ts: &TS,
+) -> Result<Option<TimeEntry>, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let mut tail = read_for_update(&t_data_file, ts)?;
+    let at_end = tail.at_end;
+    match tail.last.take().filter(|_| at_end) {
+        None => Ok(None),
+        Some(entry) => {
+            let entry = entry.with_note(Some(note));
+            replace_tail(t_data_file, &mut tail.f, tail.start_pos, &entry)?;
+            Ok(Some(entry))
+        }
+    }
+}
+
+// Insert a finished entry in order, as long as it doesn't overlap the entries on either side of it.
+pub fn insert_entry<TS: TimeSource>(entry: TimeEntry, ts: &TS) -> Result<(), Box<dyn Error>> {
+    _insert_entry(t_data_file()?, entry, ts)
+}
+
+fn _insert_entry<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    entry: TimeEntry,
+    ts: &TS,
+) -> Result<(), Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let archived = archive::list(&t_data_file)?
+        .last()
+        .map(|newest| newest.year);
+    let mut entries = open_unlocked(&t_data_file)?.read_entries(ts)?;
+    let index = insertion_point(&entries, &entry, archived)?;
+    entries.insert(index, entry.into());
+    rewrite(t_data_file, &entries)
+}
+
+/// ImportSummary says how many entries `t import` added, and which ones it left out.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    /// The entries that didn't fit in with the others, and why.
+    pub rejected: Vec<(TimeEntry, String)>,
+}
+
+// Insert finished entries from somewhere else in order. Each one is checked the same way as an
+// entry from `t add`, and the ones that don't fit are left out, along with the notes that go with
+// them. The whole import is one change, so it can be undone.
+pub fn import_entries<TS: TimeSource>(
+    new: Vec<Entry>,
+    ts: &TS,
+) -> Result<ImportSummary, Box<dyn Error>> {
+    _import_entries(t_data_file()?, new, ts)
+}
+
+fn _import_entries<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    new: Vec<Entry>,
+    ts: &TS,
+) -> Result<ImportSummary, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let archived = archive::list(&t_data_file)?
+        .last()
+        .map(|newest| newest.year);
+    let mut entries = open_unlocked(&t_data_file)?.read_entries(ts)?;
+    let mut summary = ImportSummary::default();
+    let (mut new, notes) = with_free_notes(new.into_iter().map(on_one_line).collect());
+    new.sort_by_key(|(entry, _, _)| entry.start.datetime());
+    for (entry, before, after) in new {
+        match insertion_point(&entries, &entry, archived) {
+            Ok(index) => {
+                summary.added += 1 + before.len() + after.len();
+                let inserted = before
+                    .into_iter()
+                    .chain(std::iter::once(entry.into()))
+                    .chain(after);
+                entries.splice(index..index, inserted);
             }
+            Err(e) => summary.rejected.push((entry, e)),
+        }
+    }
+    // With no entries to go with, the notes go at the end, but before an entry that's still going
+    // so that it can be stopped.
+    let end = match entries.last() {
+        Some(Entry::Time(te)) if !te.is_finished() => entries.len() - 1,
+        _ => entries.len(),
+    };
+    summary.added += notes.len();
+    entries.splice(end..end, notes);
+    if summary.added > 0 {
+        rewrite(t_data_file, &entries)?;
+    }
+    Ok(summary)
+}
+
+// Each note is a line of its own in the file, so line breaks in imported notes become spaces, the
+// same as the importers do with other runs of whitespace. Otherwise what came after a line break
+// would be read back as an entry of its own.
+fn on_one_line(entry: Entry) -> Entry {
+    let join = |text: String| {
+        if !text.contains(['\r', '\n']) {
+            return text;
+        }
+        let mut lines = text.split(['\r', '\n']);
+        let first = lines.next().unwrap_or_default().trim_end().to_string();
+        lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .fold(first, |mut joined, line| {
+                if !joined.is_empty() {
+                    joined.push(' ');
+                }
+                joined.push_str(line);
+                joined
+            })
+    };
+    match entry {
+        Entry::Time(te) => Entry::Time(TimeEntry {
+            notes: te.notes.into_iter().map(join).collect(),
+            ..te
+        }),
+        Entry::Note(text) => Entry::Note(join(text)),
+    }
+}
+
+type WithNotes = (TimeEntry, Vec<Entry>, Vec<Entry>);
+
+// Group each time entry with the notes before and after it. A note goes with the entry before it,
+// or with the first entry if there's nothing before it, like in archives. If there are no time
+// entries at all, the notes are left over.
+fn with_free_notes(new: Vec<Entry>) -> (Vec<WithNotes>, Vec<Entry>) {
+    let mut grouped: Vec<WithNotes> = vec![];
+    let mut leading_notes = vec![];
+    for entry in new {
+        match (entry, grouped.last_mut()) {
+            (Entry::Time(te), _) => grouped.push((te, std::mem::take(&mut leading_notes), vec![])),
+            (note, Some((_, _, after))) => after.push(note),
+            (note, None) => leading_notes.push(note),
+        }
+    }
+    (grouped, leading_notes)
+}
+
+// Find where a finished entry goes, as long as it doesn't overlap the entries on either side of
+// it. Notes that aren't attached to anything stay with the entry before them. Only the data file
+// gets changed, so an entry can't go back in among the archived ones.
+fn insertion_point(
+    entries: &[Entry],
+    entry: &TimeEntry,
+    archived: Option<i32>,
+) -> Result<usize, String> {
+    if !entry.is_finished() {
+        return Err("only finished entries can be added".to_string());
+    }
+    check_label(&entry.label)?;
+    let start = entry.start.datetime();
+    match archived {
+        Some(newest) if start.year() <= newest => {
+            return Err(format!(
+                "can't add an entry from {} because the entries up to {} are archived",
+                start.year(),
+                newest
+            ))
+        }
+        _ => (),
+    }
+    let index = entries
+        .iter()
+        .position(|e| matches!(e.try_time(), Some(te) if te.start.datetime() > start))
+        .unwrap_or(entries.len());
+    let prev = entries[..index].iter().rev().find_map(|e| e.try_time());
+    entry.is_valid_after(&prev.cloned())?;
+    if let Some(next) = entries[index..].iter().find_map(|e| e.try_time()) {
+        next.is_valid_after(&Some(entry.clone()))?;
+    }
+    Ok(index)
+}
+
+/// ChangeSummary describes what an undo or redo did to the file.
+pub struct ChangeSummary {
+    pub restored: Vec<Entry>,
+    pub removed: Vec<Entry>,
+}
+
+// Revert the most recent change that t made to the file. Returns None if there's nothing to undo.
+pub fn undo_change<TS: TimeSource>(ts: &TS) -> Result<Option<ChangeSummary>, Box<dyn Error>> {
+    _undo_change(t_data_file()?, ts)
+}
+
+fn _undo_change<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    ts: &TS,
+) -> Result<Option<ChangeSummary>, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let mut journal = Journal::load(&t_data_file)?;
+    let change = match journal.last_applied() {
+        None => return Ok(None),
+        Some(change) => change.clone(),
+    };
+    write_atomically(&t_data_file, &change.revert(&read_contents(&t_data_file)?)?)?;
+    journal.undo();
+    journal.save(&t_data_file)?;
+    Ok(Some(summarize(&change.old, &change.new, ts)))
+}
+
+// Re-apply the most recently undone change. Returns None if there's nothing to redo.
+pub fn redo_change<TS: TimeSource>(ts: &TS) -> Result<Option<ChangeSummary>, Box<dyn Error>> {
+    _redo_change(t_data_file()?, ts)
+}
+
+fn _redo_change<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    ts: &TS,
+) -> Result<Option<ChangeSummary>, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let mut journal = Journal::load(&t_data_file)?;
+    let change = match journal.last_undone() {
+        None => return Ok(None),
+        Some(change) => change.clone(),
+    };
+    write_atomically(&t_data_file, &change.apply(&read_contents(&t_data_file)?)?)?;
+    journal.redo();
+    journal.save(&t_data_file)?;
+    Ok(Some(summarize(&change.new, &change.old, ts)))
+}
+
+// Put a backup's contents back in place of the data file. The data file is backed up first, and
+// the change can be undone.
+pub fn restore_backup(backup: &Backup) -> Result<(), Box<dyn Error>> {
+    _restore_backup(t_data_file()?, backup)
+}
+
+fn _restore_backup<P: AsRef<Path>>(t_data_file: P, backup: &Backup) -> Result<(), Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let old = read_contents(&t_data_file)?;
+    let new = std::fs::read(&backup.path)?;
+    write_atomically(&t_data_file, &new)?;
+    record_change(t_data_file, Change::between(&old, &new))
+}
+
+// Replace the whole data file with something that came from somewhere else, like a sync upload.
+// The change goes in the journal, so that undo and redo still line up with the file.
+pub fn replace_data_file<P: AsRef<Path>>(
+    t_data_file: P,
+    contents: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let old = read_contents(&t_data_file)?;
+    write_atomically(&t_data_file, contents)?;
+    record_change(t_data_file, Change::between(&old, contents))
+}
+
+/// FormatSummary says how `t fmt` changed the file, or would change it.
+#[derive(Debug, Default, PartialEq)]
+pub struct FormatSummary {
+    /// Lines that aren't in canonical form.
+    pub changed: usize,
+    /// Blank lines, which are dropped.
+    pub blank: usize,
+}
+
+impl FormatSummary {
+    pub fn is_empty(&self) -> bool {
+        self.changed == 0 && self.blank == 0
+    }
+}
+
+// Rewrite the file in canonical form, keeping the notes where they are. Times without a UTC
+// offset keep using the local one unless explicit_tz is set. With check, nothing is written.
+pub fn format_file<TS: TimeSource>(
+    explicit_tz: bool,
+    check: bool,
+    ts: &TS,
+) -> Result<FormatSummary, Box<dyn Error>> {
+    _format_file(t_data_file()?, explicit_tz, check, ts)
+}
+
+fn _format_file<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    explicit_tz: bool,
+    check: bool,
+    ts: &TS,
+) -> Result<FormatSummary, Box<dyn Error>> {
+    let _lock = if check {
+        lock_shared(&t_data_file)?
+    } else {
+        lock_exclusive(&t_data_file)?
+    };
+    let old = read_contents(&t_data_file)?;
+    let mut entries = parse_entries(old.as_slice(), ts)
+        .map_err(|e| format!("can't format {}: {}", t_data_file.as_ref().display(), e))?;
+    if explicit_tz {
+        entries = entries
+            .into_iter()
+            .map(|e| match e {
+                Entry::Time(te) => te.with_explicit_tz().into(),
+                note => note,
+            })
+            .collect();
+    }
+    let mut new = vec![];
+    write_entries(&mut new, &entries)?;
+    let summary = compare_lines(&old, &new);
+    if !check && old != new {
+        write_atomically(&t_data_file, &new)?;
+        record_change(t_data_file, Change::between(&old, &new))?;
+    }
+    Ok(summary)
+}
+
+// Offer fixes for the problems that `t validate` finds, and write the ones that `accept` agrees
+// to. The file isn't locked while `accept` asks about them, so it's checked for changes before
+// it's written.
+pub fn repair_file<TS: TimeSource>(
+    accept: impl FnMut(&Fix) -> bool,
+    ts: &TS,
+) -> Result<Repairs, Box<dyn Error>> {
+    _repair_file(t_data_file()?, accept, ts)
+}
+
+fn _repair_file<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    accept: impl FnMut(&Fix) -> bool,
+    ts: &TS,
+) -> Result<Repairs, Box<dyn Error>> {
+    let old = {
+        let _lock = lock_shared(&t_data_file)?;
+        read_contents(&t_data_file)?
+    };
+    let mut parsed = entries(old.as_slice(), ts);
+    let mut starts = vec![];
+    let mut original = vec![];
+    while let Some(entry) = parsed.next() {
+        let entry =
+            entry.map_err(|e| format!("can't repair {}: {}", t_data_file.as_ref().display(), e))?;
+        starts.push(parsed.entry_start() as usize);
+        original.push(entry);
+    }
+    let mut entries = original.clone();
+    let repairs = repair(&mut entries, accept);
+    if !repairs.applied.is_empty() {
+        let _lock = lock_exclusive(&t_data_file)?;
+        if read_contents(&t_data_file)? != old {
+            return Err(format!(
+                "{} changed during the repair, so nothing was fixed",
+                t_data_file.as_ref().display()
+            )
+            .into());
+        }
+        let new = splice_repairs(&old, &starts, &original, &entries);
+        write_atomically(&t_data_file, &new)?;
+        record_change(t_data_file, Change::between(&old, &new))?;
+    }
+    Ok(repairs)
+}
+
+// Repairs only change the times of entries, so only the first line of each repaired entry is
+// replaced. Everything else in the file, like blank lines and notes, is left the way it was.
+fn splice_repairs(old: &[u8], starts: &[usize], original: &[Entry], repaired: &[Entry]) -> Vec<u8> {
+    let mut new = Vec::with_capacity(old.len());
+    let mut copied = 0;
+    for ((&start, before), after) in starts.iter().zip(original).zip(repaired) {
+        if before == after {
+            continue;
+        }
+        let end = old[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(old.len(), |i| start + i + 1);
+        let line = after.to_string();
+        let line = line.split_inclusive('\n').next().unwrap_or_default();
+        new.extend_from_slice(&old[copied..start]);
+        new.extend_from_slice(line.as_bytes());
+        copied = end;
+    }
+    new.extend_from_slice(&old[copied..]);
+    new
+}
+
+// Count the lines of old that don't appear in new. Formatting doesn't reorder anything, so this
+// is close enough to a diff without the cost of one.
+fn compare_lines(old: &[u8], new: &[u8]) -> FormatSummary {
+    let mut remaining: HashMap<&[u8], usize> = HashMap::new();
+    for line in new.split_inclusive(|&b| b == b'\n') {
+        *remaining.entry(line).or_default() += 1;
+    }
+    let mut summary = FormatSummary::default();
+    for line in old.split_inclusive(|&b| b == b'\n') {
+        match remaining.get_mut(line) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ if line.iter().all(u8::is_ascii_whitespace) => summary.blank += 1,
+            _ => summary.changed += 1,
+        }
+    }
+    summary
+}
+
+fn summarize<TS: TimeSource>(restored: &[u8], removed: &[u8], ts: &TS) -> ChangeSummary {
+    ChangeSummary {
+        restored: parse_entries(restored, ts).unwrap_or_default(),
+        removed: parse_entries(removed, ts).unwrap_or_default(),
+    }
+}
+
+// Replace everything from pos to the end of the file, and record the change so that it can be
+// undone.
+fn replace_tail<P: AsRef<Path>>(
+    t_data_file: P,
+    f: &mut File,
+    pos: u64,
+    tail: impl Display,
+) -> Result<(), Box<dyn Error>> {
+    let mut contents = vec![];
+    f.seek(SeekFrom::Start(0))?;
+    f.read_to_end(&mut contents)?;
+    let old = contents.split_off(pos as usize);
+    let new = tail.to_string().into_bytes();
+    contents.extend_from_slice(&new);
+    write_atomically(&t_data_file, &contents)?;
+    let pos = pos as usize;
+    record_change(t_data_file, Change { pos, old, new })
+}
+
+// Replace the whole file, and record the change so that it can be undone.
+fn rewrite<P: AsRef<Path>>(t_data_file: P, entries: &[Entry]) -> Result<(), Box<dyn Error>> {
+    let mut new = vec![];
+    write_entries(&mut new, entries)?;
+    let old = read_contents(&t_data_file)?;
+    write_atomically(&t_data_file, &new)?;
+    record_change(t_data_file, Change::between(&old, &new))
+}
+
+// t.csv has already been changed by now, so a journal that can't be read is started over instead
+// of failing. Only the changes before this one can't be undone.
+fn record_change<P: AsRef<Path>>(t_data_file: P, change: Change) -> Result<(), Box<dyn Error>> {
+    let mut journal = Journal::load(&t_data_file).unwrap_or_else(|e| {
+        eprintln!("warning: {}, so it was started over", e);
+        Journal::default()
+    });
+    journal.record(change);
+    journal.save(t_data_file)?;
+    Ok(())
+}
+
+fn read_contents<P: AsRef<Path>>(t_data_file: P) -> io::Result<Vec<u8>> {
+    match std::fs::read(t_data_file) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+// The new contents are written to a temporary file first so that a failure part way through
+// doesn't leave a truncated t.csv behind. The old contents are kept as a backup.
+fn write_atomically<P: AsRef<Path>>(t_data_file: P, contents: &[u8]) -> io::Result<()> {
+    let path = &resolve_links(t_data_file.as_ref().to_path_buf());
+    let tmp = tmp_path(path);
+    let mut f = File::create(&tmp)?;
+    f.write_all(contents)?;
+    let before = std::fs::metadata(path).ok();
+    if let Some(metadata) = &before {
+        f.set_permissions(metadata.permissions())?;
+    }
+    f.sync_all()?;
+    let old = read_contents(path)?;
+    backup::back_up(path, backups_to_keep(), OffsetDateTime::now_utc())?;
+    std::fs::rename(&tmp, path)?;
+    // The rename isn't durable until the directory is synced too.
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all()?,
+        _ => (),
+    }
+    // The totals are still right without the cache, so a cache that can't be kept up to date,
+    // e.g. in a read-only directory, just means that they take longer.
+    let _ = cache::track_write(path, before.as_ref(), &old, contents);
+    Ok(())
+}
+
+// A config error would already have been reported when the data file was found.
+fn backups_to_keep() -> usize {
+    Config::current()
+        .ok()
+        .and_then(|config| config.backups)
+        .unwrap_or(backup::DEFAULT_BACKUPS)
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+// Labels are the rest of the line, so they can't span lines, and a label that ends in the resume
+// marker would be read back as a resumed entry.
+fn check_label(label: &Option<String>) -> Result<(), String> {
+    match label {
+        Some(label) if label.contains(['\r', '\n']) => {
+            Err(format!("label {:?} can't contain a newline", label))
         }
+        Some(label) if label.rsplit(',').next().map(str::trim) == Some(RESUME_MARKER) => Err(
+            format!("label {:?} can't end in \",{}\"", label, RESUME_MARKER),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn check_not_future<TS: TimeSource>(
+    at: Option<OffsetDateTime>,
+    ts: &TS,
+) -> Result<OffsetDateTime, Box<dyn Error>> {
+    let now = ts.now();
+    match at {
+        None => Ok(now),
+        Some(at) if at > now => Err(format!("{} is in the future", Time::at(at)).into()),
+        Some(at) => Ok(at),
     }
 }
 
-type ReadResult = (File, Option<TimeEntry>, u64, u64);
+/// Tail is the end of the data file, which is all that most changes need to look at.
+struct Tail {
+    f: File,
+    /// The last time entry, even if there are free notes after it.
+    last: Option<TimeEntry>,
+    /// Whether the last time entry is the last entry in the file, so it can be rewritten in place.
+    at_end: bool,
+    /// Where the last entry in the file starts and stops.
+    start_pos: u64,
+    stop_pos: u64,
+}
 
-// Get the last entry from the file, along with its start and stop
-// positions.
+// Get the last time entry from the file, along with where the last entry starts and stops.
 fn read_for_update<P: AsRef<Path>, TS: TimeSource>(
     t_data_file: P,
     ts: &TS,
-) -> Result<ReadResult, Box<dyn Error>> {
+) -> Result<Tail, Box<dyn Error>> {
     let mut f = OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
         .truncate(false)
         .open(t_data_file)?;
-
-    seek_last_entries(&mut f, 2)?;
+    seek_last_time_entry(&mut f)?;
 
     let mut r = BufReader::new(f);
-    let mut last_entry = None;
-    let mut last_time_entry_is_finished = true;
+    let mut last = None;
+    let mut at_end = false;
     let mut start_pos = get_pos(&mut r)?;
     let mut stop_pos = start_pos;
 
@@ -456,26 +2039,46 @@ fn read_for_update<P: AsRef<Path>, TS: TimeSource>(
         match entry {
             None => break,
             Some(Entry::Time(te)) => {
-                last_time_entry_is_finished = te.is_finished();
-                last_entry = Some(te);
-            }
-            _ => {
-                last_entry = None;
+                last = Some(te);
+                at_end = true;
             }
+            _ => at_end = false,
         };
         start_pos = stop_pos;
         stop_pos = get_pos(&mut r)?;
     }
 
-    if last_entry.is_none() && !last_time_entry_is_finished {
-        panic!("last time entry is open and annotation comes after it (todo - make this an Err)");
+    if matches!(&last, Some(te) if !te.is_finished() && !at_end) {
+        return Err(
+            "the last time entry is still going, but there's a note after it (use 't edit' to fix it)"
+                .into(),
+        );
     }
 
-    Ok((r.into_inner(), last_entry, start_pos, stop_pos))
+    Ok(Tail {
+        f: r.into_inner(),
+        last,
+        at_end,
+        start_pos,
+        stop_pos,
+    })
 }
 
 pub fn read_entries<TS: TimeSource>(ts: &TS) -> Result<Vec<Entry>, Box<dyn Error>> {
-    t_open(t_data_file()?)?.read_entries(ts)
+    Ok(t_open(t_data_file()?)?.read_entries(ts)?)
+}
+
+/// Read the entries that can be parsed, along with the syntax errors for the lines that can't.
+pub fn read_entries_recovering<TS: TimeSource>(ts: &TS) -> Result<Parsed, Box<dyn Error>> {
+    Ok(t_open(t_data_file()?)?.read_entries_recovering(ts)?)
+}
+
+/// Like `read_entries_recovering`, but without the archives, so that the line numbers in the
+/// errors are the ones in the data file.
+pub fn read_live_entries_recovering<TS: TimeSource>(ts: &TS) -> Result<Parsed, Box<dyn Error>> {
+    let t_data_file = t_data_file()?;
+    let _lock = lock_shared(&t_data_file)?;
+    Ok(open_unlocked(&t_data_file)?.read_entries_recovering(ts)?)
 }
 
 pub fn read_time_entries<TS: TimeSource>(ts: &TS) -> Result<Vec<TimeEntry>, Box<dyn Error>> {
@@ -483,36 +2086,160 @@ pub fn read_time_entries<TS: TimeSource>(ts: &TS) -> Result<Vec<TimeEntry>, Box<
 }
 
 pub fn read_last_entry<TS: TimeSource>(ts: &TS) -> Result<Option<Entry>, Box<dyn Error>> {
-    t_open(t_data_file()?)?.read_last_entry(ts)
+    Ok(t_open(t_data_file()?)?.read_last_entry(ts)?)
 }
 
 pub fn read_last_entries<TS: TimeSource>(n: u64, ts: &TS) -> Result<Vec<Entry>, Box<dyn Error>> {
-    t_open(t_data_file()?)?.read_last_entries(n, ts)
+    Ok(t_open(t_data_file()?)?.read_last_entries(n, ts)?)
 }
 
+pub fn read_last_entries_recovering<TS: TimeSource>(
+    n: u64,
+    ts: &TS,
+) -> Result<Parsed, Box<dyn Error>> {
+    Ok(t_open(t_data_file()?)?.read_last_entries_recovering(n, ts)?)
+}
+
+pub fn read_entries_since<TS: TimeSource>(
+    since: OffsetDateTime,
+    ts: &TS,
+) -> Result<Vec<Entry>, Box<dyn Error>> {
+    Ok(t_open(t_data_file()?)?.read_entries_since(since, ts)?)
+}
+
+pub fn read_entries_since_recovering<TS: TimeSource>(
+    since: OffsetDateTime,
+    ts: &TS,
+) -> Result<Parsed, Box<dyn Error>> {
+    Ok(t_open(t_data_file()?)?.read_entries_since_recovering(since, ts)?)
+}
+
+// Add up the time in each week. The weeks that are done come from the cache beside the data file.
+pub fn read_weekly_totals<TS: TimeSource>(
+    cal: &Calendar,
+    ts: &TS,
+) -> Result<Totals, Box<dyn Error>> {
+    cache::weekly_totals(t_data_file()?, cal, ts)
+}
+
+// Move the finished entries from before the given year out of the data file and into yearly
+// archives beside it. Returns each archive that was added to and how many time entries went into
+// it.
+pub fn archive_entries<TS: TimeSource>(
+    before: i32,
+    ts: &TS,
+) -> Result<Vec<(PathBuf, usize)>, Box<dyn Error>> {
+    _archive_entries(t_data_file()?, before, ts)
+}
+
+fn _archive_entries<P: AsRef<Path>, TS: TimeSource>(
+    t_data_file: P,
+    before: i32,
+    ts: &TS,
+) -> Result<Vec<(PathBuf, usize)>, Box<dyn Error>> {
+    let _lock = lock_exclusive(&t_data_file)?;
+    let entries = open_unlocked(&t_data_file)?
+        .read_entries(ts)
+        .map_err(|e| format!("can't archive {}: {}", t_data_file.as_ref().display(), e))?;
+    let (years, live) = archive::split(entries, before);
+    if let (Some(newest), Some((year, _))) = (archive::list(&t_data_file)?.last(), years.first()) {
+        if newest.year > *year {
+            return Err(format!(
+                "can't archive entries from {} after {} already has newer ones",
+                year,
+                newest.path.display()
+            )
+            .into());
+        }
+    }
+
+    // The archives are written first, so if something goes wrong, entries end up in both places
+    // rather than in neither.
+    let mut archived = vec![];
+    for (year, entries) in years {
+        let path = archive::path(&t_data_file, year);
+        let mut contents = read_contents(&path)?;
+        write_entries(&mut contents, &entries)?;
+        write_atomically(&path, &contents)?;
+        archived.push((path, into_time_entries(entries).len()));
+    }
+    if !archived.is_empty() {
+        let mut contents = vec![];
+        write_entries(&mut contents, &live)?;
+        write_atomically(&t_data_file, &contents)?;
+        // Undoing an earlier change would put back entries that are in the archives now.
+        Journal::default().save(&t_data_file)?;
+    }
+    Ok(archived)
+}
+
+// Open the data file and its archives for reading. They can't be changed until the TFile is
+// dropped.
 pub fn t_open<P: AsRef<Path>>(t_data_file: P) -> io::Result<TFile> {
+    let tf = open_unlocked(&t_data_file)?;
+    let archives = archive::list(&t_data_file)?;
+    let lock = match (&tf.f, archives.is_empty()) {
+        (None, true) => None,
+        _ => Some(lock_shared(&t_data_file)?),
+    };
+    let archives = archives
+        .into_iter()
+        .map(|archive| File::open(archive.path))
+        .collect::<io::Result<_>>()?;
+    Ok(TFile {
+        archives,
+        _lock: lock,
+        ..tf
+    })
+}
+
+// Writers already have an exclusive lock, and a shared lock on top of it would block. They only
+// ever touch the data file, so the archives aren't opened.
+fn open_unlocked<P: AsRef<Path>>(t_data_file: P) -> io::Result<TFile> {
     match File::open(t_data_file) {
-        Ok(f) => Ok(TFile { f: Some(f) }),
+        Ok(f) => Ok(TFile {
+            archives: vec![],
+            f: Some(f),
+            _lock: None,
+        }),
         Err(e) => match e.kind() {
-            ErrorKind::NotFound => Ok(TFile { f: None }),
+            ErrorKind::NotFound => Ok(TFile {
+                archives: vec![],
+                f: None,
+                _lock: None,
+            }),
             _ => Err(e),
         },
     }
 }
 
+/// TFile reads the archives, oldest first, and then the data file, as one history.
 pub struct TFile {
+    archives: Vec<File>,
     f: Option<File>,
+    _lock: Option<DataLock>,
 }
 
 impl TFile {
-    pub fn read_entries<TS: TimeSource>(self, ts: &TS) -> Result<Vec<Entry>, Box<dyn Error>> {
-        match self.f {
-            Some(f) => parse_entries(f, ts),
-            None => Ok(vec![]),
+    pub fn read_entries<TS: TimeSource>(self, ts: &TS) -> Result<Vec<Entry>, ParseError> {
+        let mut entries = vec![];
+        for f in self.files() {
+            entries.extend(parse_entries(f, ts)?);
+        }
+        Ok(entries)
+    }
+
+    pub fn read_entries_recovering<TS: TimeSource>(self, ts: &TS) -> Result<Parsed, ParseError> {
+        let mut parsed = Parsed::default();
+        for f in self.files() {
+            let more = parse_entries_recovering(f, ts)?;
+            parsed.entries.extend(more.entries);
+            parsed.errors.extend(more.errors);
         }
+        Ok(parsed)
     }
 
-    fn read_last_entry<TS: TimeSource>(self, ts: &TS) -> Result<Option<Entry>, Box<dyn Error>> {
+    fn read_last_entry<TS: TimeSource>(self, ts: &TS) -> Result<Option<Entry>, ParseError> {
         Ok(self.read_last_entries(1, ts)?.into_iter().last())
     }
 
@@ -520,37 +2247,230 @@ impl TFile {
         self,
         n: u64,
         ts: &TS,
-    ) -> Result<Vec<Entry>, Box<dyn Error>> {
-        match self.f {
-            None => Ok(vec![]),
-            Some(mut f) => {
-                seek_last_entries(&mut f, n)?;
-                parse_entries(f, ts)
+    ) -> Result<Vec<Entry>, ParseError> {
+        Ok(self.read_last(n, |f| strictly(f, ts))?.entries)
+    }
+
+    pub fn read_last_entries_recovering<TS: TimeSource>(
+        self,
+        n: u64,
+        ts: &TS,
+    ) -> Result<Parsed, ParseError> {
+        self.read_last(n, |f| parse_entries_recovering(f, ts))
+    }
+
+    /// Read the entries that might overlap the time since `since`: the last one that started
+    /// before then, and everything after it.
+    pub fn read_entries_since<TS: TimeSource>(
+        self,
+        since: OffsetDateTime,
+        ts: &TS,
+    ) -> Result<Vec<Entry>, ParseError> {
+        Ok(self.read_since(since, ts, |f| strictly(f, ts))?.entries)
+    }
+
+    pub fn read_entries_since_recovering<TS: TimeSource>(
+        self,
+        since: OffsetDateTime,
+        ts: &TS,
+    ) -> Result<Parsed, ParseError> {
+        self.read_since(since, ts, |f| parse_entries_recovering(f, ts))
+    }
+
+    fn files(self) -> Vec<File> {
+        let mut files = self.archives;
+        files.extend(self.f);
+        files
+    }
+
+    // Go back through the files from the newest until there are n entries.
+    fn read_last(
+        self,
+        n: u64,
+        parse: impl Fn(File) -> Result<Parsed, ParseError>,
+    ) -> Result<Parsed, ParseError> {
+        let mut parsed = Parsed::default();
+        for mut f in self.files().into_iter().rev() {
+            let wanted = n.saturating_sub(parsed.entries.len() as u64);
+            if wanted == 0 {
+                break;
+            }
+            seek_last_entries(&mut f, wanted)?;
+            let mut more = parse(f)?;
+            more.entries = keep_last(more.entries, wanted);
+            prepend(&mut parsed, more);
+        }
+        Ok(parsed)
+    }
+
+    // Go back through the files from the newest until one has an entry from before `since`.
+    fn read_since<TS: TimeSource>(
+        self,
+        since: OffsetDateTime,
+        ts: &TS,
+        parse: impl Fn(File) -> Result<Parsed, ParseError>,
+    ) -> Result<Parsed, ParseError> {
+        let mut parsed = Parsed::default();
+        for mut f in self.files().into_iter().rev() {
+            let found = seek_entries_since(&mut f, since, ts)?;
+            prepend(&mut parsed, parse(f)?);
+            if found {
+                break;
             }
         }
+        Ok(parsed)
     }
 }
 
+fn prepend(parsed: &mut Parsed, mut earlier: Parsed) {
+    earlier.entries.append(&mut parsed.entries);
+    earlier.errors.append(&mut parsed.errors);
+    *parsed = earlier;
+}
+
+fn strictly<TS: TimeSource>(f: File, ts: &TS) -> Result<Parsed, ParseError> {
+    Ok(Parsed {
+        entries: parse_entries(f, ts)?,
+        errors: vec![],
+    })
+}
+
+// Notes at the start of a tail can be split into more entries than it looked like there were.
+fn keep_last(mut entries: Vec<Entry>, n: u64) -> Vec<Entry> {
+    let extra = entries.len().saturating_sub(n as usize);
+    entries.split_off(extra)
+}
+
+/// Move to the start of the nth entry from the end of the file, or to the start of the file if
+/// there aren't that many entries.
 fn seek_last_entries(f: &mut File, n: u64) -> io::Result<()> {
-    let len = f.metadata()?.len();
-    let off = n * APPROX_LINE_LENGTH_FOR_SEEK;
-    if off < len {
-        f.seek(SeekFrom::Start(len - off))?;
-        read_to(f, b'\n')?;
+    let mut lines = ReverseLines::new(f)?;
+    let mut found = 0;
+    while found < n {
+        match lines.next_line()? {
+            None => return seek_to(f, 0),
+            Some((pos, line)) => {
+                if starts_entry(&line) {
+                    found += 1;
+                    if found == n {
+                        return seek_to(f, pos);
+                    }
+                }
+            }
+        }
+    }
+    seek_to(f, f.metadata()?.len())
+}
+
+/// Move to the start of the last time entry, skipping back over any free notes after it, or to the
+/// start of the file if there isn't one.
+fn seek_last_time_entry(f: &mut File) -> io::Result<()> {
+    let mut lines = ReverseLines::new(f)?;
+    while let Some((pos, line)) = lines.next_line()? {
+        if starts_entry(&line) && line.first() != Some(&b'#') {
+            return seek_to(f, pos);
+        }
+    }
+    seek_to(f, 0)
+}
+
+/// Move to the start of the last time entry that started before `since`, or to the start of the
+/// file if there isn't one. Lines that can't be parsed are skipped over. Returns whether there was
+/// one.
+fn seek_entries_since<TS: TimeSource>(
+    f: &mut File,
+    since: OffsetDateTime,
+    ts: &TS,
+) -> io::Result<bool> {
+    let mut lines = ReverseLines::new(f)?;
+    while let Some((pos, line)) = lines.next_line()? {
+        if !starts_entry(&line) {
+            continue;
+        }
+        if let Ok((Some(Entry::Time(te)), _)) = parse_entry(line.as_slice(), ts) {
+            if te.start.datetime() < since {
+                seek_to(f, pos)?;
+                return Ok(true);
+            }
+        }
     }
+    seek_to(f, 0)?;
+    Ok(false)
+}
+
+fn seek_to(f: &mut File, pos: u64) -> io::Result<()> {
+    f.seek(SeekFrom::Start(pos))?;
     Ok(())
 }
 
-fn get_pos<S: Seek>(mut f: S) -> io::Result<u64> {
-    f.stream_position()
+/// A line starts a new entry unless it's blank or it's an indented note, which belongs to the
+/// entry before it.
+fn starts_entry(line: &[u8]) -> bool {
+    match line.iter().position(|c| *c != b' ') {
+        None => false,
+        Some(0) => true,
+        Some(i) => line[i] != b'#',
+    }
 }
 
-fn read_to(f: &mut File, c: u8) -> io::Result<()> {
-    let mut buf = [0; 1];
-    loop {
-        let res = f.read(&mut buf)?;
-        if res == 0 || buf[0] == c {
-            return Ok(());
+const REVERSE_CHUNK_SIZE: u64 = 4096;
+
+/// ReverseLines reads a file one line at a time from the end, without reading the parts of the
+/// file that come before the lines it returns.
+struct ReverseLines<'a> {
+    f: &'a mut File,
+    /// The part of the file that's been read but not returned yet, and where it starts.
+    buf: Vec<u8>,
+    buf_pos: u64,
+}
+
+impl<'a> ReverseLines<'a> {
+    fn new(f: &'a mut File) -> io::Result<Self> {
+        let len = f.metadata()?.len();
+        Ok(Self {
+            f,
+            buf: vec![],
+            buf_pos: len,
+        })
+    }
+
+    /// The previous line and the position that it starts at, without its newline.
+    fn next_line(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        if self.buf.is_empty() && !self.read_chunk()? {
+            return Ok(None);
+        }
+        // The newline at the very end belongs to the line that's being returned.
+        if self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+        }
+        loop {
+            if let Some(i) = self.buf.iter().rposition(|c| *c == b'\n') {
+                let line = self.buf.split_off(i + 1);
+                return Ok(Some((self.buf_pos + i as u64 + 1, line)));
+            }
+            if !self.read_chunk()? {
+                return Ok(Some((0, std::mem::take(&mut self.buf))));
+            }
+        }
+    }
+
+    /// Read the chunk before the buffer, or return false if the buffer starts at the beginning
+    /// of the file.
+    fn read_chunk(&mut self) -> io::Result<bool> {
+        if self.buf_pos == 0 {
+            return Ok(false);
         }
+        let start = self.buf_pos.saturating_sub(REVERSE_CHUNK_SIZE);
+        let mut chunk = vec![0; (self.buf_pos - start) as usize];
+        self.f.seek(SeekFrom::Start(start))?;
+        self.f.read_exact(&mut chunk)?;
+        chunk.append(&mut self.buf);
+        self.buf = chunk;
+        self.buf_pos = start;
+        Ok(true)
     }
 }
+
+fn get_pos<S: Seek>(mut f: S) -> io::Result<u64> {
+    f.stream_position()
+}
@@ -1,4 +1,5 @@
 use crate::entry::TimeEntry;
+use std::collections::BTreeMap;
 
 pub fn filter_entries(
     entries: Vec<TimeEntry>,
@@ -8,6 +9,19 @@ pub fn filter_entries(
     Ok(entries.into_iter().filter(|e| filter.filter(e)).collect())
 }
 
+pub fn filter_by_label(entries: Vec<TimeEntry>, label: &str) -> Vec<TimeEntry> {
+    entries.into_iter().filter(|e| e.has_label(label)).collect()
+}
+
+/// Splits entries up by label. Entries without a label are grouped under None, which sorts first.
+pub fn group_by_label(entries: Vec<TimeEntry>) -> BTreeMap<Option<String>, Vec<TimeEntry>> {
+    let mut groups: BTreeMap<Option<String>, Vec<TimeEntry>> = BTreeMap::new();
+    for entry in entries {
+        groups.entry(entry.label.clone()).or_default().push(entry);
+    }
+    groups
+}
+
 fn build_filter(filters: Vec<String>) -> Result<Filter, &'static str> {
     match filters.len() {
         0 => Ok(Filter::None),
@@ -0,0 +1,467 @@
+use crate::entry::{Entry, TimeEntry};
+use crate::export::JsonEntry;
+use crate::zone::Zone;
+use std::error::Error;
+use std::io::Read;
+use std::str::FromStr;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+/// Format is a kind of file from another time tracker that `t import` can read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// One of Timewarrior's data files, e.g. ~/.timewarrior/data/2023-01.data.
+    Timewarrior,
+    /// A detailed report exported from Toggl as CSV.
+    TogglCsv,
+    /// What `t export json` or `t export ndjson` wrote, either way.
+    Json,
+    /// Check-ins and check-outs in the timeclock format that hledger and ledger read.
+    Timeclock,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timewarrior" => Ok(Self::Timewarrior),
+            "toggl-csv" => Ok(Self::TogglCsv),
+            "json" | "ndjson" => Ok(Self::Json),
+            "timeclock" => Ok(Self::Timeclock),
+            _ => Err(format!(
+                "can't import from {s:?}, only from timewarrior, toggl-csv, json, ndjson or timeclock"
+            )),
+        }
+    }
+}
+
+/// Foreign is what could be made out of another time tracker's records.
+#[derive(Debug, Default, PartialEq)]
+pub struct Foreign {
+    pub entries: Vec<Entry>,
+    /// The records that couldn't be converted, and why.
+    pub skipped: Vec<String>,
+}
+
+/// Convert another time tracker's records into time entries. Times that don't say what time zone
+/// they're in are taken to be in `zone`.
+pub fn read<R: Read>(mut r: R, format: Format, zone: &Zone) -> Result<Foreign, Box<dyn Error>> {
+    let mut contents = String::new();
+    r.read_to_string(&mut contents)?;
+    let contents = contents.trim_start_matches('\u{feff}');
+    match format {
+        Format::Timewarrior => Ok(read_timewarrior(contents, zone)),
+        Format::TogglCsv => read_toggl_csv(contents, zone),
+        Format::Json => read_json(contents),
+        Format::Timeclock => Ok(read_timeclock(contents, zone)),
+    }
+}
+
+// Each check-in is followed by a check-out, like
+//   i 2023/01/02 09:00:00 acme  code review
+//   o 2023/01/02 10:30:00
+// where the account ends at two spaces and the rest is the description. The account becomes the
+// label, unless it can't be one because it has a comma, and then it's a note.
+fn read_timeclock(contents: &str, zone: &Zone) -> Foreign {
+    let mut foreign = Foreign::default();
+    let mut clocked_in: Option<(usize, OffsetDateTime, &str)> = None;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with([';', '#', '*']) {
+            continue;
+        }
+        let mut chars = line.chars();
+        let code = chars.next();
+        let rest = chars.as_str();
+        let checked = match code {
+            Some('i' | 'o' | 'O') => timeclock_time(rest, zone),
+            _ => Err("expected a line that starts with \"i\" or \"o\"".to_string()),
+        };
+        let checked = checked.and_then(|(at, rest)| match (code == Some('i'), clocked_in) {
+            (true, None) => {
+                clocked_in = Some((i, at, rest));
+                Ok(())
+            }
+            (true, Some(_)) => Err("checked in again without checking out".to_string()),
+            (false, Some((_, start, rest))) => {
+                clocked_in = None;
+                let (account, description) = rest.split_once("  ").unwrap_or((rest, ""));
+                let entry = if account.contains(',') {
+                    let note = format!("{account} {description}");
+                    entry(start, at, String::new(), note)
+                } else {
+                    entry(start, at, account.to_string(), description.to_string())
+                };
+                foreign.entries.push(entry.into());
+                Ok(())
+            }
+            (false, None) => Err("checked out without checking in".to_string()),
+        });
+        if let Err(e) = checked {
+            foreign.skipped.push(format!("line {}: {}", i + 1, e));
+        }
+    }
+    if let Some((i, _, _)) = clocked_in {
+        foreign
+            .skipped
+            .push(format!("line {}: never checked out", i + 1));
+    }
+    foreign
+}
+
+// The date and time after "i" or "o", and whatever follows them.
+fn timeclock_time<'a>(rest: &'a str, zone: &Zone) -> Result<(OffsetDateTime, &'a str), String> {
+    let rest = rest
+        .strip_prefix(' ')
+        .ok_or("expected a space after \"i\" or \"o\"")?;
+    let mut parts = rest.splitn(3, ' ');
+    let (date, time) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let date = Date::parse(date, "%Y/%m/%d").or_else(|_| Date::parse(date, "%Y-%m-%d"));
+    let time = Time::parse(time, "%H:%M:%S").or_else(|_| Time::parse(time, "%H:%M"));
+    match (date, time) {
+        (Ok(date), Ok(time)) => Ok((
+            zone.assume(date.with_time(time)),
+            parts.next().unwrap_or("").trim(),
+        )),
+        _ => Err(format!(
+            "expected a date and time like 2023/01/02 09:00:00, not {rest:?}"
+        )),
+    }
+}
+
+// A JSON export is an array, and an NDJSON export has an object on each line.
+fn read_json(contents: &str) -> Result<Foreign, Box<dyn Error>> {
+    let records: Vec<(String, Result<JsonEntry, serde_json::Error>)> =
+        if contents.trim_start().starts_with('[') {
+            let values: Vec<serde_json::Value> = serde_json::from_str(contents)?;
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| (format!("record {}", i + 1), serde_json::from_value(value)))
+                .collect()
+        } else {
+            contents
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| (format!("line {}", i + 1), serde_json::from_str(line)))
+                .collect()
+        };
+    let mut foreign = Foreign::default();
+    for (place, record) in records {
+        match record
+            .map_err(|e| e.to_string())
+            .and_then(JsonEntry::into_entry)
+        {
+            Ok(entry) => foreign.entries.push(entry),
+            Err(e) => foreign.skipped.push(format!("{place}: {e}")),
+        }
+    }
+    Ok(foreign)
+}
+
+// Each line is an interval, like
+//   inc 20230102T140000Z - 20230102T153000Z # acme "code review" # "a note"
+// with the tags after the first # and the annotation after the second one.
+fn read_timewarrior(contents: &str, zone: &Zone) -> Foreign {
+    let mut foreign = Foreign::default();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match timewarrior_interval(line, zone) {
+            Ok(entry) => foreign.entries.push(entry.into()),
+            Err(e) => foreign.skipped.push(format!("line {}: {}", i + 1, e)),
+        }
+    }
+    foreign
+}
+
+fn timewarrior_interval(line: &str, zone: &Zone) -> Result<TimeEntry, String> {
+    let rest = line
+        .strip_prefix("inc ")
+        .ok_or("expected an interval that starts with \"inc\"")?;
+    let (times, extra) = match rest.find('#') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let (start, stop) = match times.trim().split_once(" - ") {
+        Some((start, stop)) => (start, stop),
+        None => return Err("the interval is still running".to_string()),
+    };
+    let parse = |s: &str| {
+        PrimitiveDateTime::parse(s.trim(), "%Y%m%dT%H%M%SZ")
+            .map(|dt| {
+                let dt = dt.assume_utc();
+                dt.to_offset(zone.offset_of(dt))
+            })
+            .map_err(|_| format!("expected a time like 20230102T140000Z, not {s:?}"))
+    };
+    let mut words = words(extra).into_iter();
+    let tags: Vec<String> = words.by_ref().take_while(|word| word != "#").collect();
+    let annotation: Vec<String> = words.collect();
+    Ok(entry(
+        parse(start)?,
+        parse(stop)?,
+        tags.join(" "),
+        annotation.join(" "),
+    ))
+}
+
+// Split on spaces, except inside double quotes, where \" is a quote.
+fn words(s: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => continue,
+            '"' => {
+                let mut word = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        c => word.push(c),
+                    }
+                }
+                words.push(word);
+            }
+            c => {
+                let mut word = c.to_string();
+                word.extend(chars.by_ref().take_while(|c| *c != ' '));
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+// Toggl's detailed report has a header row, and local times with the dates and times in separate
+// columns.
+fn read_toggl_csv(contents: &str, zone: &Zone) -> Result<Foreign, Box<dyn Error>> {
+    let mut records = csv_records(contents)?.into_iter();
+    let header = records.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let required = |name: &str| {
+        column(name)
+            .ok_or_else(|| format!("this isn't a Toggl CSV export, it has no {name} column"))
+    };
+    let (start_date, start_time) = (required("Start date")?, required("Start time")?);
+    let (end_date, end_time) = (required("End date")?, required("End time")?);
+    let (project, description) = (column("Project"), column("Description"));
+
+    let mut foreign = Foreign::default();
+    for (i, record) in records.enumerate() {
+        let field = |i: Option<usize>| i.and_then(|i| record.get(i)).map_or("", String::as_str);
+        let parse = |date: usize, time: usize| {
+            let (date, time) = (field(Some(date)), field(Some(time)));
+            match (Date::parse(date, "%Y-%m-%d"), Time::parse(time, "%H:%M:%S")) {
+                (Ok(date), Ok(time)) => Ok(zone.assume(date.with_time(time))),
+                _ => Err(format!(
+                    "expected a date and time like 2023-01-02 09:00:00, not {date:?} {time:?}"
+                )),
+            }
+        };
+        let converted = parse(start_date, start_time).and_then(|start| {
+            Ok(entry(
+                start,
+                parse(end_date, end_time)?,
+                field(project).to_string(),
+                field(description).to_string(),
+            ))
+        });
+        match converted {
+            Ok(entry) => foreign.entries.push(entry.into()),
+            // The header is row 1.
+            Err(e) => foreign.skipped.push(format!("row {}: {}", i + 2, e)),
+        }
+    }
+    Ok(foreign)
+}
+
+fn csv_records(contents: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut chars = contents.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("the CSV ends in the middle of a quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+// t only keeps whole minutes, and a note has to fit on one line.
+fn entry(start: OffsetDateTime, stop: OffsetDateTime, label: String, note: String) -> TimeEntry {
+    let minute = |dt: OffsetDateTime| dt - time::Duration::seconds(dt.second() as i64);
+    let note: Vec<&str> = note.split_whitespace().collect();
+    let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+    TimeEntry::start_at(minute(start))
+        .finish_at(minute(stop))
+        .with_label(non_empty(label))
+        .with_note(non_empty(note.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, Foreign, Format};
+    use crate::entry::Entry;
+    use pretty_assertions::assert_eq;
+    use time::{date, offset, UtcOffset};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    const OFFSET: UtcOffset = offset!(-05:00);
+
+    fn entry(start: (u8, u8), stop: (u8, u8), label: &str, note: &str) -> Entry {
+        let at = |(h, m)| {
+            date!(2023 - 01 - 02)
+                .with_time(time::Time::try_from_hms(h, m, 0).unwrap())
+                .assume_offset(OFFSET)
+        };
+        super::entry(at(start), at(stop), label.to_string(), note.to_string()).into()
+    }
+
+    #[test]
+    fn test_read_timewarrior() -> TestRes {
+        let input = "inc 20230102T140000Z - 20230102T153010Z # acme \"code review\" # \"a \\\"long\\\" note\"\n\
+                     \n\
+                     inc 20230102T160000Z - 20230102T170000Z\n\
+                     inc 20230102T180000Z - 20230102T183000Z # # \"just a note\"\n\
+                     inc 20230102T190000Z # acme\n\
+                     exc monday <8:00:00\n";
+        let foreign = read(input.as_bytes(), Format::Timewarrior, &OFFSET.into())?;
+        assert_eq!(
+            Foreign {
+                entries: vec![
+                    entry((9, 0), (10, 30), "acme code review", "a \"long\" note"),
+                    entry((11, 0), (12, 0), "", ""),
+                    entry((13, 0), (13, 30), "", "just a note"),
+                ],
+                skipped: vec![
+                    "line 5: the interval is still running".to_string(),
+                    "line 6: expected an interval that starts with \"inc\"".to_string(),
+                ],
+            },
+            foreign
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_toggl_csv() -> TestRes {
+        let input = "\u{feff}User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags,Amount ()\r\n\
+                     Pat,pat@example.com,,acme,,\"reviews, mostly\nand \"\"planning\"\"\",No,2023-01-02,09:00:00,2023-01-02,10:30:00,01:30:00,,\r\n\
+                     Pat,pat@example.com,,,,,No,2023-01-02,11:00:00,2023-01-02,12:00:00,01:00:00,,\r\n\
+                     Pat,pat@example.com,,acme,,,No,2023-01-02,soon,2023-01-02,14:00:00,,,\r\n";
+        let foreign = read(input.as_bytes(), Format::TogglCsv, &OFFSET.into())?;
+        assert_eq!(
+            Foreign {
+                entries: vec![
+                    entry((9, 0), (10, 30), "acme", "reviews, mostly and \"planning\""),
+                    entry((11, 0), (12, 0), "", ""),
+                ],
+                skipped: vec![
+                    "row 4: expected a date and time like 2023-01-02 09:00:00, not \"2023-01-02\" \"soon\""
+                        .to_string()
+                ],
+            },
+            foreign
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_other_csv() {
+        let result = read("a,b\n1,2\n".as_bytes(), Format::TogglCsv, &OFFSET.into());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_read_json() -> TestRes {
+        let record = |start: &str| {
+            format!(
+                "{{\"type\":\"time\",\"start\":{{\"time\":\"2023-01-02T{start}-05:00\"}},\
+                 \"stop\":{{\"time\":\"2023-01-02T10:30:00-05:00\"}},\"label\":\"acme\"}}"
+            )
+        };
+        let note = "{\"type\":\"note\",\"text\":\" a note\"}";
+        let expected = Foreign {
+            entries: vec![
+                entry((9, 0), (10, 30), "acme", ""),
+                Entry::Note(" a note".into()),
+            ],
+            skipped: vec![],
+        };
+        let json = format!("[\n  {},\n  {}\n]\n", record("09:00:00"), note);
+        assert_eq!(
+            expected,
+            read(json.as_bytes(), Format::Json, &OFFSET.into())?
+        );
+        let ndjson = format!("{}\n{}\n\n{}\n", record("09:00:00"), note, record("soon"));
+        let foreign = read(ndjson.as_bytes(), Format::Json, &OFFSET.into())?;
+        assert_eq!(expected.entries, foreign.entries);
+        assert_eq!(
+            vec!["line 4: expected a time like 2023-01-02T09:00:00-05:00, not \"2023-01-02Tsoon-05:00\"".to_string()],
+            foreign.skipped
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_timeclock() -> TestRes {
+        let input = "; from hledger\n\
+                     i 2023/01/02 09:00:00 acme  code  review\n\
+                     o 2023/01/02 10:30:59\n\
+                     \n\
+                     i 2023-01-02 11:00\n\
+                     O 2023-01-02 12:00 acme\n\
+                     o 2023/01/02 12:30:00\n\
+                     i 2023/01/02 13:00:00 lunch, early\n\
+                     o 2023/01/02 13:30:00\n\
+                     h 2023/01/02 14:00:00\n\
+                     i 2023/01/02 soon\n\
+                     i 2023/01/02 17:00:00 acme\n";
+        let foreign = read(input.as_bytes(), Format::Timeclock, &OFFSET.into())?;
+        assert_eq!(
+            Foreign {
+                entries: vec![
+                    entry((9, 0), (10, 30), "acme", "code review"),
+                    entry((11, 0), (12, 0), "", ""),
+                    entry((13, 0), (13, 30), "", "lunch, early"),
+                ],
+                skipped: vec![
+                    "line 7: checked out without checking in".to_string(),
+                    "line 10: expected a line that starts with \"i\" or \"o\"".to_string(),
+                    "line 11: expected a date and time like 2023/01/02 09:00:00, not \"2023/01/02 soon\""
+                        .to_string(),
+                    "line 12: never checked out".to_string(),
+                ],
+            },
+            foreign
+        );
+        Ok(())
+    }
+}
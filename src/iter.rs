@@ -1,120 +1,120 @@
+use crate::calendar::Calendar;
 use crate::entry::TimeEntry;
 use crate::timesource::TimeSource;
-use time::{Date, Duration, OffsetDateTime, Weekday::*};
+use std::iter::Peekable;
+use time::{Date, Duration, OffsetDateTime};
 
-pub fn each_week<TS: TimeSource>(entries: Vec<TimeEntry>, ts: &TS) -> DaysIterator {
+pub fn each_week<I: IntoIterator<Item = TimeEntry>, TS: TimeSource>(
+    entries: I,
+    cal: &Calendar,
+    ts: &TS,
+) -> DaysIterator<I::IntoIter> {
     DaysIterator {
-        entries,
+        entries: entries.into_iter().peekable(),
+        cal: *cal,
         days: 7,
         last_date: None,
-        next_index: 0,
         now: ts.now(),
     }
 }
 
-fn each_day<TS: TimeSource>(entries: Vec<TimeEntry>, ts: &TS) -> DaysIterator {
+fn each_day<I: IntoIterator<Item = TimeEntry>, TS: TimeSource>(
+    entries: I,
+    cal: &Calendar,
+    ts: &TS,
+) -> DaysIterator<I::IntoIter> {
     DaysIterator {
-        entries,
+        entries: entries.into_iter().peekable(),
+        cal: *cal,
         days: 1,
         last_date: None,
-        next_index: 0,
         now: ts.now(),
     }
 }
 
-pub fn each_day_in_week<TS: TimeSource>(
-    entries: Vec<TimeEntry>,
+pub fn each_day_in_week<I: IntoIterator<Item = TimeEntry>, TS: TimeSource>(
+    entries: I,
     week_start: Date,
+    cal: &Calendar,
     ts: &TS,
-) -> WeekOfDaysIterator {
+) -> WeekOfDaysIterator<I::IntoIter> {
     WeekOfDaysIterator {
-        days_iter: each_day(entries, ts),
+        days_iter: each_day(entries, cal, ts),
         week_start,
     }
 }
 
-pub struct DaysIterator {
-    entries: Vec<TimeEntry>,
+/// DaysIterator groups entries by day or by week. The entries are consumed as it goes, so they
+/// can come straight from parser::entries. An entry that spans more than one group is in each of
+/// them.
+pub struct DaysIterator<I: Iterator<Item = TimeEntry>> {
+    entries: Peekable<I>,
+    cal: Calendar,
     days: u8,
     last_date: Option<Date>,
-    next_index: usize,
     now: OffsetDateTime,
 }
 
-impl Iterator for DaysIterator {
+impl<I: Iterator<Item = TimeEntry>> Iterator for DaysIterator<I> {
     type Item = (Date, Vec<TimeEntry>);
 
     fn next(&mut self) -> std::option::Option<Self::Item> {
-        if self.next_index >= self.entries.len() {
-            None
-        } else {
-            let date = match self.last_date {
-                None => self.get_first_date(),
-                Some(d) => d + self.span(),
+        let first_workday = self.cal.workday(self.entries.peek()?.start.datetime());
+        let date = match self.last_date {
+            None => self.get_first_date(first_workday),
+            Some(d) => d + self.span(),
+        };
+        let next_date = date + self.span();
+        self.last_date = Some(date);
+        let mut entries = vec![];
+        while let Some(entry) = self.entries.peek() {
+            if self.cal.workday(entry.start.datetime()) >= next_date {
+                break;
+            }
+            entries.push(entry.clone().finish_if_not(self.now));
+            // An entry that stops right as a day starts doesn't go on into that day.
+            let st = match &entry.stop {
+                None => self.cal.workday(self.now),
+                Some(t) => self.cal.workday(t.datetime() - Duration::minute()),
             };
-            let next_date = date + self.span();
-            self.last_date = Some(date);
-            let mut entries = vec![];
-            for entry in self.entries.iter().skip(self.next_index) {
-                if entry.start_date() >= next_date {
-                    break;
-                } else {
-                    entries.push(entry.clone().finish_if_not(self.now));
-                    let st = match entry.stop_date() {
-                        None => self.now.date(),
-                        Some(d) => d,
-                    };
-                    if st >= next_date {
-                        break;
-                    }
-                    self.next_index += 1;
-                }
+            if st >= next_date {
+                break;
             }
-            Some((date, entries))
+            self.entries.next();
         }
+        Some((date, entries))
     }
 }
 
-impl DaysIterator {
+impl<I: Iterator<Item = TimeEntry>> DaysIterator<I> {
     fn span(&self) -> Duration {
         Duration::days(self.days as i64)
     }
 
-    fn get_first_date(&self) -> Date {
+    fn get_first_date(&self, first_workday: Date) -> Date {
         match self.days {
-            1 => self.entries[0].start_date(),
-            7 => {
-                let date = self.entries[0].start_date();
-                match date.weekday() {
-                    Sunday => date,
-                    Monday => date - Duration::days(1),
-                    Tuesday => date - Duration::days(2),
-                    Wednesday => date - Duration::days(3),
-                    Thursday => date - Duration::days(4),
-                    Friday => date - Duration::days(5),
-                    Saturday => date - Duration::days(6),
-                }
-            }
+            1 => first_workday,
+            7 => self.cal.start_of_week(first_workday),
             x => panic!("Unable to iterate with span of {} days!", x),
         }
     }
 }
 
-const SUNDAY_TO_SATURDAY: Duration = Duration::days(6);
+const SIX_DAYS: Duration = Duration::days(6);
 
-pub struct WeekOfDaysIterator {
-    days_iter: DaysIterator,
+pub struct WeekOfDaysIterator<I: Iterator<Item = TimeEntry>> {
+    days_iter: DaysIterator<I>,
     week_start: Date,
 }
 
-impl Iterator for WeekOfDaysIterator {
+impl<I: Iterator<Item = TimeEntry>> Iterator for WeekOfDaysIterator<I> {
     type Item = (Date, Vec<TimeEntry>);
 
     fn next(&mut self) -> std::option::Option<Self::Item> {
         match self.days_iter.next() {
             None => None,
             Some((d, e)) => {
-                if d < self.week_start || d > (self.week_start + SUNDAY_TO_SATURDAY) {
+                if d < self.week_start || d > (self.week_start + SIX_DAYS) {
                     self.next()
                 } else {
                     Some((d, e))
@@ -126,23 +126,24 @@ impl Iterator for WeekOfDaysIterator {
 
 #[cfg(test)]
 mod tests {
+    use crate::calendar::Calendar;
     use crate::parser::parse_time_entries;
     use crate::timesource::mock_time::mock_time;
     use crate::timesource::real_time::DefaultTimeSource;
     use pretty_assertions::assert_eq;
-    use time::{date, offset, time};
+    use time::{date, offset, time, Weekday};
 
     type TestRes = Result<(), Box<dyn std::error::Error>>;
 
     #[test]
     pub fn test_each_week_empty() {
-        let mut i = super::each_week(vec![], &DefaultTimeSource);
+        let mut i = super::each_week(vec![], &Calendar::default(), &DefaultTimeSource);
         assert_eq!(None, i.next());
     }
 
     #[test]
     pub fn test_each_day_empty() {
-        let mut i = super::each_day(vec![], &DefaultTimeSource);
+        let mut i = super::each_day(vec![], &Calendar::default(), &DefaultTimeSource);
         assert_eq!(None, i.next());
     }
 
@@ -154,12 +155,35 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_week(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_week(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(Some((date!(2020 - 08 - 02), entries)), i.next());
         assert_eq!(None, i.next());
         Ok(())
     }
 
+    #[test]
+    pub fn test_each_week_streaming() -> TestRes {
+        let original = "2020-08-02 10:10,2020-08-02 11:10\n\
+                        # a note\n\
+                        2020-08-08 23:10,2020-08-09 01:10\n\
+                        2020-08-10 10:10,2020-08-10 11:10\n";
+        let entries = parse_time_entries(original.as_bytes(), &DefaultTimeSource)?;
+        let stream = crate::parser::entries(original.as_bytes(), &DefaultTimeSource)
+            .time_entries()
+            .map(Result::unwrap);
+        let mut i = super::each_week(stream, &Calendar::default(), &DefaultTimeSource);
+        assert_eq!(
+            Some((date!(2020 - 08 - 02), entries[..2].to_vec())),
+            i.next()
+        );
+        assert_eq!(
+            Some((date!(2020 - 08 - 09), entries[1..].to_vec())),
+            i.next()
+        );
+        assert_eq!(None, i.next());
+        Ok(())
+    }
+
     #[test]
     pub fn test_each_day_one_day() -> TestRes {
         let entries = parse_time_entries(
@@ -168,7 +192,7 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_day(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_day(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(Some((date!(2020 - 08 - 02), entries)), i.next());
         assert_eq!(None, i.next());
         Ok(())
@@ -182,7 +206,7 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_week(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_week(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(
             Some((date!(2020 - 08 - 02), entries.drain(..1).collect())),
             i.next()
@@ -203,7 +227,7 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_day(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_day(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(
             Some((date!(2020 - 08 - 02), entries.drain(..1).collect())),
             i.next()
@@ -224,7 +248,7 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_week(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_week(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(
             Some((
                 date!(2020 - 08 - 02),
@@ -246,6 +270,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    pub fn test_each_day_with_rollover() -> TestRes {
+        let entries = parse_time_entries(
+            "2020-08-02 22:10,2020-08-03 02:10\n\
+             2020-08-03 03:00,2020-08-03 05:00\n\
+             2020-08-03 10:10,2020-08-03 11:10\n"
+                .as_bytes(),
+            &DefaultTimeSource,
+        )?;
+        let cal = Calendar {
+            day_rollover: time!(04:00),
+            ..Calendar::default()
+        };
+        let mut i = super::each_day(entries.clone(), &cal, &DefaultTimeSource);
+        assert_eq!(
+            Some((
+                date!(2020 - 08 - 02),
+                vec![entries[0].clone(), entries[1].clone()]
+            )),
+            i.next()
+        );
+        assert_eq!(
+            Some((
+                date!(2020 - 08 - 03),
+                vec![entries[1].clone(), entries[2].clone()]
+            )),
+            i.next()
+        );
+        assert_eq!(None, i.next());
+        Ok(())
+    }
+
     #[test]
     pub fn test_each_day_entry_spans_days() -> TestRes {
         let entries = parse_time_entries(
@@ -255,7 +311,7 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_day(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_day(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(
             Some((
                 date!(2020 - 08 - 02),
@@ -282,12 +338,37 @@ mod tests {
             "2020-08-08 10:10,2020-08-08 11:10\n".as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i = super::each_week(entries.clone(), &DefaultTimeSource);
+        let mut i = super::each_week(entries.clone(), &Calendar::default(), &DefaultTimeSource);
         assert_eq!(Some((date!(2020 - 08 - 02), entries)), i.next());
         assert_eq!(None, i.next());
         Ok(())
     }
 
+    #[test]
+    pub fn test_each_week_starting_monday() -> TestRes {
+        let entries = parse_time_entries(
+            "2020-08-02 10:10,2020-08-02 11:10\n\
+             2020-08-03 10:10,2020-08-03 11:10\n"
+                .as_bytes(),
+            &DefaultTimeSource,
+        )?;
+        let cal = Calendar {
+            week_start: Weekday::Monday,
+            ..Calendar::default()
+        };
+        let mut i = super::each_week(entries.clone(), &cal, &DefaultTimeSource);
+        assert_eq!(
+            Some((date!(2020 - 07 - 27), vec![entries[0].clone()])),
+            i.next()
+        );
+        assert_eq!(
+            Some((date!(2020 - 08 - 03), vec![entries[1].clone()])),
+            i.next()
+        );
+        assert_eq!(None, i.next());
+        Ok(())
+    }
+
     #[test]
     fn test_each_week_current_entry_in_progress() -> TestRes {
         let ts = mock_time(date!(2020 - 01 - 15), time!(11:00), offset!(-04:00));
@@ -296,7 +377,7 @@ mod tests {
             "2020-01-15 10:10 -0400,2020-01-15 11:00 -0400".as_bytes(),
             &ts,
         )?;
-        let mut i = super::each_week(entries.clone(), &ts);
+        let mut i = super::each_week(entries.clone(), &Calendar::default(), &ts);
         assert_eq!(Some((date!(2020 - 01 - 12), expected_entries)), i.next());
         assert_eq!(None, i.next());
         Ok(())
@@ -313,8 +394,12 @@ mod tests {
                 .as_bytes(),
             &DefaultTimeSource,
         )?;
-        let mut i =
-            super::each_day_in_week(entries.clone(), date!(2020 - 08 - 03), &DefaultTimeSource);
+        let mut i = super::each_day_in_week(
+            entries.clone(),
+            date!(2020 - 08 - 03),
+            &Calendar::default(),
+            &DefaultTimeSource,
+        );
         assert_eq!(
             Some((date!(2020 - 08 - 03), vec![entries[1].clone()])),
             i.next()
@@ -0,0 +1,289 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "t journal 1";
+
+/// How many changes are kept around to be undone.
+const MAX_CHANGES: usize = 20;
+
+/// Change is a splice of t.csv: the bytes in `old` at `pos` were replaced with the bytes in `new`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub pos: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl Change {
+    /// Describe the difference between two versions of the file. The change is widened to whole
+    /// lines so that the old and new parts can be parsed as entries.
+    pub fn between(old: &[u8], new: &[u8]) -> Self {
+        let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        while prefix > 0 && old[prefix - 1] != b'\n' {
+            prefix -= 1;
+        }
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while suffix > 0 && suffix < max_suffix && old[old.len() - suffix - 1] != b'\n' {
+            suffix -= 1;
+        }
+        Self {
+            pos: prefix,
+            old: old[prefix..old.len() - suffix].to_vec(),
+            new: new[prefix..new.len() - suffix].to_vec(),
+        }
+    }
+
+    /// Replace `old` with `new` in the given contents.
+    pub fn apply(&self, contents: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        splice(contents, self.pos, &self.old, &self.new)
+    }
+
+    /// Replace `new` with `old` in the given contents.
+    pub fn revert(&self, contents: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        splice(contents, self.pos, &self.new, &self.old)
+    }
+}
+
+fn splice(contents: &[u8], pos: usize, from: &[u8], to: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let end = pos + from.len();
+    if contents.get(pos..end) != Some(from) {
+        return Err("t.csv has been changed since this change was recorded".into());
+    }
+    let mut res = Vec::with_capacity(contents.len() - from.len() + to.len());
+    res.extend_from_slice(&contents[..pos]);
+    res.extend_from_slice(to);
+    res.extend_from_slice(&contents[end..]);
+    Ok(res)
+}
+
+/// Journal keeps the most recent changes to t.csv, so that they can be undone and redone.
+#[derive(Debug, Default, PartialEq)]
+pub struct Journal {
+    /// Changes that are currently in effect, oldest first.
+    applied: Vec<Change>,
+    /// Changes that were undone, most recently undone last.
+    undone: Vec<Change>,
+}
+
+impl Journal {
+    pub fn load<P: AsRef<Path>>(t_data_file: P) -> Result<Self, Box<dyn Error>> {
+        match File::open(journal_path(t_data_file.as_ref())) {
+            Ok(f) => read_journal(BufReader::new(f)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace the journal on disk. Like t.csv, it's written to a temporary file first, so that
+    /// it's never left half written.
+    pub fn save<P: AsRef<Path>>(&self, t_data_file: P) -> io::Result<()> {
+        let path = journal_path(t_data_file.as_ref());
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut f = File::create(&tmp)?;
+        self.write(&mut f)?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &path)
+    }
+
+    /// Record a new change. Anything that was undone can't be redone after this.
+    pub fn record(&mut self, change: Change) {
+        self.undone.clear();
+        self.applied.push(change);
+        if self.applied.len() > MAX_CHANGES {
+            self.applied.remove(0);
+        }
+    }
+
+    pub fn last_applied(&self) -> Option<&Change> {
+        self.applied.last()
+    }
+
+    pub fn last_undone(&self) -> Option<&Change> {
+        self.undone.last()
+    }
+
+    /// Move the most recent change onto the redo stack.
+    pub fn undo(&mut self) {
+        if let Some(change) = self.applied.pop() {
+            self.undone.push(change);
+        }
+    }
+
+    /// Move the most recently undone change back onto the undo stack.
+    pub fn redo(&mut self) {
+        if let Some(change) = self.undone.pop() {
+            self.applied.push(change);
+        }
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "{HEADER}")?;
+        for (kind, changes) in [("applied", &self.applied), ("undone", &self.undone)] {
+            for change in changes {
+                writeln!(
+                    w,
+                    "{} {} {} {}",
+                    kind,
+                    change.pos,
+                    change.old.len(),
+                    change.new.len()
+                )?;
+                w.write_all(&change.old)?;
+                w.write_all(&change.new)?;
+                writeln!(w)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_journal(mut r: impl BufRead) -> Result<Journal, Box<dyn Error>> {
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+    if line.trim_end() != HEADER {
+        return Err(corrupt("unrecognized header"));
+    }
+    let mut journal = Journal::default();
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(journal);
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (kind, pos, old_len, new_len) = match fields.as_slice() {
+            [kind, pos, old_len, new_len] => (
+                *kind,
+                pos.parse().map_err(|_| corrupt("bad position"))?,
+                old_len.parse().map_err(|_| corrupt("bad length"))?,
+                new_len.parse().map_err(|_| corrupt("bad length"))?,
+            ),
+            _ => return Err(corrupt("expected a change")),
+        };
+        let mut old = vec![0; old_len];
+        let mut new = vec![0; new_len];
+        let mut nl = [0; 1];
+        r.read_exact(&mut old)
+            .and_then(|_| r.read_exact(&mut new))
+            .and_then(|_| r.read_exact(&mut nl))
+            .map_err(|_| corrupt("truncated change"))?;
+        let change = Change { pos, old, new };
+        match kind {
+            "applied" => journal.applied.push(change),
+            "undone" => journal.undone.push(change),
+            _ => return Err(corrupt("unrecognized change")),
+        };
+    }
+}
+
+fn corrupt(message: &str) -> Box<dyn Error> {
+    format!("the undo journal is corrupt: {message}").into()
+}
+
+fn journal_path(t_data_file: &Path) -> PathBuf {
+    let mut name = t_data_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".journal");
+    t_data_file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_journal, Change, Journal};
+    use pretty_assertions::assert_eq;
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    fn change(pos: usize, old: &str, new: &str) -> Change {
+        Change {
+            pos,
+            old: old.as_bytes().to_vec(),
+            new: new.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_between_append() {
+        assert_eq!(
+            change(6, "", "line2\n"),
+            Change::between(b"line1\n", b"line1\nline2\n")
+        );
+    }
+
+    #[test]
+    fn test_between_finish_last_line() {
+        assert_eq!(
+            change(6, "a,\n", "a,b\n"),
+            Change::between(b"line1\na,\n", b"line1\na,b\n")
+        );
+    }
+
+    #[test]
+    fn test_between_insert_in_middle() {
+        assert_eq!(
+            change(6, "", "new\n"),
+            Change::between(b"line1\nline3\n", b"line1\nnew\nline3\n")
+        );
+    }
+
+    #[test]
+    fn test_between_same_prefix_on_changed_line() {
+        assert_eq!(
+            change(6, "abc\n", "abd\n"),
+            Change::between(b"line1\nabc\nline3\n", b"line1\nabd\nline3\n")
+        );
+    }
+
+    #[test]
+    fn test_apply_and_revert() -> TestRes {
+        let old = b"line1\nline3\n";
+        let new = b"line1\nline2\nline3\n";
+        let change = Change::between(old, new);
+        assert_eq!(new.to_vec(), change.apply(old)?);
+        assert_eq!(old.to_vec(), change.revert(new)?);
+        assert!(change.revert(old).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> TestRes {
+        let mut journal = Journal::default();
+        journal.record(change(0, "", "a\n"));
+        journal.record(change(2, "b\n", "b,c\n\n"));
+        journal.record(change(6, "", "d\n"));
+        journal.undo();
+        let mut buf = vec![];
+        journal.write(&mut buf)?;
+        assert_eq!(journal, read_journal(&buf[..])?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        let mut journal = Journal::default();
+        journal.record(change(0, "", "a\n"));
+        journal.undo();
+        assert!(journal.last_undone().is_some());
+        journal.record(change(0, "", "b\n"));
+        assert_eq!(None, journal.last_undone());
+        assert_eq!(Some(&change(0, "", "b\n")), journal.last_applied());
+    }
+
+    #[test]
+    fn test_record_keeps_recent_changes() {
+        let mut journal = Journal::default();
+        for i in 0..30 {
+            journal.record(change(i, "", "x\n"));
+        }
+        assert_eq!(super::MAX_CHANGES, journal.applied.len());
+        assert_eq!(10, journal.applied[0].pos);
+    }
+}
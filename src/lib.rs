@@ -1,9 +1,21 @@
+pub mod archive;
+pub mod backup;
+pub mod cache;
+pub mod calendar;
+pub mod config;
 pub mod entry;
+pub mod export;
 pub mod extents;
 pub mod file;
 pub mod filter;
+pub mod import;
 pub mod iter;
+pub mod journal;
+pub mod lock;
 pub mod parser;
 pub mod query;
+pub mod repair;
 pub mod report;
 pub mod timesource;
+pub mod totals;
+pub mod zone;
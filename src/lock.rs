@@ -0,0 +1,110 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// How long to wait for another process to finish with the data file before giving up.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/// DataLock is an advisory lock on a data file. It's released when it's dropped.
+///
+/// The lock is taken on a `.lock` file next to the data file rather than on the data file itself,
+/// because the data file gets replaced when it's rewritten.
+pub struct DataLock {
+    _f: Option<File>,
+}
+
+/// Lock the data file for writing. Nobody else can read or write it until the lock is dropped.
+pub fn lock_exclusive<P: AsRef<Path>>(t_data_file: P) -> io::Result<DataLock> {
+    let f = open_lock_file(t_data_file.as_ref())?;
+    wait_for_lock(t_data_file.as_ref(), &f, File::try_lock)?;
+    Ok(DataLock { _f: Some(f) })
+}
+
+/// Lock the data file for reading. Other readers can still read it, but nobody can write it until
+/// the lock is dropped. If the lock file can't be created, e.g. because the data file is in a
+/// read-only directory, the file is read without a lock.
+pub fn lock_shared<P: AsRef<Path>>(t_data_file: P) -> io::Result<DataLock> {
+    let f = match open_lock_file(t_data_file.as_ref()) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => return Ok(DataLock { _f: None }),
+        Err(e) => return Err(e),
+    };
+    wait_for_lock(t_data_file.as_ref(), &f, File::try_lock_shared)?;
+    Ok(DataLock { _f: Some(f) })
+}
+
+fn open_lock_file(t_data_file: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(t_data_file))
+}
+
+fn wait_for_lock(
+    t_data_file: &Path,
+    f: &File,
+    try_lock: fn(&File) -> Result<(), TryLockError>,
+) -> io::Result<()> {
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    loop {
+        match try_lock(f) {
+            Ok(()) => return Ok(()),
+            Err(TryLockError::Error(e)) => return Err(e),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                sleep(LOCK_RETRY_INTERVAL)
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    ErrorKind::WouldBlock,
+                    format!(
+                        "{} is in use by another t process, try again in a moment",
+                        t_data_file.display()
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+fn lock_path(t_data_file: &Path) -> PathBuf {
+    let mut name = t_data_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    t_data_file.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_exclusive, lock_shared};
+    use std::io::ErrorKind;
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_shared_locks() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("t.csv");
+        let _a = lock_shared(&path)?;
+        let _b = lock_shared(&path)?;
+        let err = lock_exclusive(&path).err().expect("the file is being read");
+        assert_eq!(ErrorKind::WouldBlock, err.kind());
+        assert!(err.to_string().contains("in use by another t process"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclusive_lock() -> TestRes {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("t.csv");
+        let lock = lock_exclusive(&path)?;
+        assert!(lock_shared(&path).is_err());
+        assert!(lock_exclusive(&path).is_err());
+        drop(lock);
+        lock_exclusive(&path)?;
+        Ok(())
+    }
+}
@@ -2,23 +2,34 @@ mod sync;
 mod web;
 
 use gumdrop::Options;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::TryInto;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::os::unix::process::CommandExt;
+use std::io::{BufRead, BufReader, Write};
 use std::str::FromStr;
+use std::sync::OnceLock;
+use t::backup;
+use t::cache::Totals;
+use t::calendar::{parse_weekday, Calendar};
+use t::config::Config;
 use t::entry::into_time_entries;
 use t::entry::Entry;
 use t::entry::TimeEntry;
+use t::export;
 use t::extents;
 use t::file::*;
-use t::filter::filter_entries;
+use t::filter::{filter_by_label, filter_entries, group_by_label};
+use t::import;
+use t::lock;
+use t::parser::{ParseError, Parsed};
 use t::query::{self, EntriesResult};
 use t::report;
 use t::timesource::real_time::DefaultTimeSource;
-use time::{Duration, OffsetDateTime};
+use t::timesource::TimeSource;
+use t::totals::WeekTotals;
+use t::zone;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
 
 const DEFAULT_SPARKS: [char; 7] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇'];
 
@@ -27,30 +38,91 @@ const HOUR_EMOJI: [char; 12] = [
 ];
 const CHECK_EMOJI: char = '✅';
 
+// These defaults can be changed in config.toml.
 const FULL_WEEK: i64 = 5 * 8 * 60; // 5 day, 8 hours per day, 60 minutes per hour.
 const MY_FULL_WEEK: i64 = 2000; // This is my goal.
+const DAY_LEGEND: &str = "8h=480m";
+const WEEK_LEGEND: &str = "8h=480m 16h=960m 24h=1440m 32h=1920m 40h=2400m";
 
 #[derive(Options)]
 struct MainOptions {
     #[options(command)]
     command: Option<TCommand>,
 
+    #[options(
+        no_short,
+        help = "time entry database (default: $T_DATA_FILE, data_file in config.toml, or ~/.t.csv)"
+    )]
+    data_file: Option<String>,
+
+    #[options(
+        no_short,
+        help = "first day of the week, e.g. monday (default: $T_WEEK_START, week_start in config.toml, or sunday)"
+    )]
+    week_start: Option<WeekdayArg>,
+
+    #[options(
+        no_short,
+        meta = "MINUTES",
+        help = "weekly goal in minutes (default: weekly_goal in config.toml, or 2400)"
+    )]
+    goal: Option<i64>,
+
+    #[options(
+        no_short,
+        meta = "CHARS",
+        help = "characters for the spark graphs, from smallest to largest (default: sparks in config.toml)"
+    )]
+    sparks: Option<String>,
+
+    #[options(
+        no_short,
+        meta = "TEXT",
+        help = "legend under the daily reports (default: day_legend in config.toml)"
+    )]
+    day_legend: Option<String>,
+
+    #[options(
+        no_short,
+        meta = "TEXT",
+        help = "legend under the weekly reports (default: week_legend in config.toml)"
+    )]
+    week_legend: Option<String>,
+
     #[options(help = "show this help message")]
     help: bool,
 }
 
 #[derive(Options)]
 enum TCommand {
-    #[options(help = "start a time entry")]
-    Start(NoArgs),
+    #[options(help = "start a time entry, optionally with a project label")]
+    Start(StartArgs),
     #[options(help = "stop a time entry")]
-    Stop(NoArgs),
+    Stop(StopArgs),
+    #[options(help = "stop the current time entry and start one with a new label")]
+    Switch(StartArgs),
+    #[options(help = "stop a time entry for a break")]
+    Pause(PauseArgs),
+    #[options(help = "continue the last time entry's session after a break")]
+    Resume(PauseArgs),
+    #[options(help = "attach a note to the current or last time entry")]
+    Note(NoteArgs),
+    #[options(help = "add a finished time entry, e.g. 't add 2024-03-04 13:00 14:30'")]
+    Add(AddArgs),
+    #[options(help = "undo the last change to the time entry database")]
+    Undo(NoArgs),
+    #[options(help = "redo the last change that was undone")]
+    Redo(NoArgs),
     #[options(help = "edit the time entry database in $EDITOR")]
     Edit(NoArgs),
+    #[options(help = "list the backups of the time entry database, or restore one")]
+    Backups(BackupsArgs),
     #[options(help = "show current status")]
     Status(StatusArgs),
     #[options(help = "list times worked on a given day or during a range of days")]
     List(ListArgs),
+    #[options(help = "list work sessions and the breaks in them, for a day or a range of days")]
+    Breaks(ListArgs),
     #[options(help = "generate output for bitbar")]
     Bitbar(BitBarArgs),
     #[options(help = "show time worked today")]
@@ -60,11 +132,13 @@ enum TCommand {
     #[options(help = "compare my current progress this week against previous weeks")]
     Race(RaceArgs),
     #[options(help = "show spark graph of all entries")]
-    All(NoArgs),
+    All(AllArgs),
     #[options(help = "show a table of time worked per day")]
     Days(DaysArgs),
     #[options(help = "produce a CSV report (see help for options)")]
     Csv(CSVArgs),
+    #[options(help = "write entries for another program, e.g. 't export ics' for a calendar")]
+    Export(ExportArgs),
     #[options(
         help = "show the amount of time off I took per year, with optional number of minutes per full time week"
     )]
@@ -73,6 +147,14 @@ enum TCommand {
     Path(NoArgs),
     #[options(help = "check for any formatting errors in t.csv")]
     Validate(ValidateArgs),
+    #[options(help = "rewrite t.csv in canonical form")]
+    Fmt(FmtArgs),
+    #[options(help = "fix the problems that validate finds, asking about each one")]
+    Repair(RepairArgs),
+    #[options(help = "move old entries into yearly archives, e.g. 't archive --before 2023'")]
+    Archive(ArchiveArgs),
+    #[options(help = "add the entries from another time tracker's file")]
+    Import(ImportArgs),
     #[options(help = "show current timestamp as it would be written to t.csv")]
     Now(NoArgs),
     #[options(help = "show all annotations in t.csv")]
@@ -83,6 +165,61 @@ enum TCommand {
     Sync(SyncArgs),
 }
 
+#[derive(Options, Default)]
+struct StartArgs {
+    #[options(free, help = "project or tag to record with the entry")]
+    label: Option<String>,
+    #[options(help = "start time, as HH:MM (today) or YYYY-MM-DD HH:MM")]
+    at: Option<AtArg>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options, Default)]
+struct StopArgs {
+    #[options(short = "m", help = "attach a note to the entry")]
+    message: Option<String>,
+    #[options(help = "stop time, as HH:MM (today) or YYYY-MM-DD HH:MM")]
+    at: Option<AtArg>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options)]
+struct PauseArgs {
+    #[options(help = "time of the pause or resume, as HH:MM (today) or YYYY-MM-DD HH:MM")]
+    at: Option<AtArg>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options)]
+struct NoteArgs {
+    #[options(free, help = "text of the note")]
+    text: Vec<String>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options)]
+struct AddArgs {
+    #[options(free, help = "date of the entry (YYYY-MM-DD)")]
+    date: Option<DateArg>,
+    #[options(free, help = "start time (HH:MM)")]
+    start: Option<AtArg>,
+    #[options(
+        free,
+        help = "stop time (HH:MM, or YYYY-MM-DD HH:MM if it's on a later day)"
+    )]
+    stop: Option<AtArg>,
+    #[options(help = "project or tag to record with the entry")]
+    label: Option<String>,
+    #[options(short = "m", help = "attach a note to the entry")]
+    message: Option<String>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
 #[derive(Options)]
 struct StatusArgs {
     #[options(help = "also calculate the time worked this week so far")]
@@ -101,6 +238,28 @@ struct ListArgs {
     help: bool,
 }
 
+#[derive(Options)]
+struct ExportArgs {
+    #[options(free, help = "what to write: ics, json, ndjson or timeclock")]
+    format: Option<export::Format>,
+    #[options(free, help = "start date of export (optional, default is everything)")]
+    start: Option<DateArg>,
+    #[options(free, help = "end date of export (optional, default is one day)")]
+    stop: Option<DateArg>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+struct WeekdayArg(time::Weekday);
+
+impl FromStr for WeekdayArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(parse_weekday(s)?))
+    }
+}
+
 struct DateArg {
     date: time::Date,
 }
@@ -109,13 +268,48 @@ impl FromStr for DateArg {
     type Err = time::ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        eprintln!("parsing {s:?}");
         Ok(Self {
             date: time::Date::parse(s, "%F")?,
         })
     }
 }
 
+/// A time given on the command line. Without a date, it's a time today.
+struct AtArg {
+    date: Option<time::Date>,
+    time: time::Time,
+}
+
+impl FromStr for AtArg {
+    type Err = time::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match PrimitiveDateTime::parse(s, "%F %H:%M") {
+            Ok(dt) => Ok(Self {
+                date: Some(dt.date()),
+                time: dt.time(),
+            }),
+            Err(_) => Ok(Self {
+                date: None,
+                time: time::Time::parse(s, "%H:%M")?,
+            }),
+        }
+    }
+}
+
+impl AtArg {
+    fn resolve(&self) -> OffsetDateTime {
+        self.resolve_on(TIME_SOURCE.now().date())
+    }
+
+    fn resolve_on(&self, default_date: time::Date) -> OffsetDateTime {
+        let date = self.date.unwrap_or(default_date);
+        TIME_SOURCE
+            .zone()
+            .assume(PrimitiveDateTime::new(date, self.time))
+    }
+}
+
 #[derive(Options)]
 struct BitBarArgs {
     #[options(help = "bitbar plugin script")]
@@ -126,12 +320,26 @@ struct BitBarArgs {
     help: bool,
 }
 
+#[derive(Options)]
+struct AllArgs {
+    #[options(help = "only include entries with this label")]
+    label: Option<String>,
+    #[options(help = "show a separate report for each label")]
+    by_label: bool,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
 #[derive(Options)]
 struct DaysArgs {
     #[options(help = "include monthly and yearly totals")]
     summary: bool,
     #[options(help = "only show yearly totals")]
     annual: bool,
+    #[options(help = "only include entries with this label")]
+    label: Option<String>,
+    #[options(help = "show a separate report for each label")]
+    by_label: bool,
     #[options(
         free,
         help = "A year (YYYY) or month (YYYY-MM) to show (default is all)"
@@ -151,6 +359,45 @@ struct ValidateArgs {
     verbose: bool,
 }
 
+#[derive(Options)]
+struct FmtArgs {
+    #[options(help = "don't change anything, and exit with 1 if t.csv isn't formatted")]
+    check: bool,
+    #[options(help = "write the UTC offset on times that don't have one")]
+    explicit_tz: bool,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options)]
+struct ArchiveArgs {
+    #[options(required, help = "archive the finished entries from before this year")]
+    before: i32,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options)]
+struct ImportArgs {
+    #[options(free, help = "the file to import")]
+    file: Option<String>,
+    #[options(
+        required,
+        help = "what kind of file it is: timewarrior, toggl-csv, json, ndjson or timeclock"
+    )]
+    from: Option<import::Format>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
+#[derive(Options)]
+struct RepairArgs {
+    #[options(help = "make every fix without asking")]
+    yes: bool,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
 impl From<DaysArgs> for (Vec<String>, report::days::Options) {
     fn from(val: DaysArgs) -> Self {
         (
@@ -180,6 +427,12 @@ struct CSVArgs {
     )]
     report_type: Option<ReportType>,
 
+    #[options(help = "only include entries with this label")]
+    label: Option<String>,
+
+    #[options(help = "add a label column and report each label separately")]
+    by_label: bool,
+
     #[options(help = "show this message")]
     help: bool,
 }
@@ -203,6 +456,10 @@ impl ReportType {
 struct PtoArgs {
     #[options(free)]
     full_week: Option<i64>,
+    #[options(help = "only include entries with this label")]
+    label: Option<String>,
+    #[options(help = "show a separate report for each label")]
+    by_label: bool,
     #[options(help = "show this message")]
     help: bool,
 }
@@ -212,7 +469,9 @@ struct WebArgs {
     #[options(help = "path to static files")]
     static_path: Option<String>,
 
-    #[options(help = "path to t.csv (default: T_DATA_FILE or t.csv)")]
+    #[options(
+        help = "path to t.csv (default: --data-file, $T_DATA_FILE, data_file in config.toml, or ~/.t.csv)"
+    )]
     t_data_file: Option<String>,
 
     #[options(help = "show this message")]
@@ -227,13 +486,13 @@ impl From<WebArgs> for web::Options {
         };
         let t_data_file = val
             .t_data_file
-            .or_else(|| t::file::t_data_file().ok())
-            .unwrap_or_else(|| "t.csv".to_string())
+            .unwrap_or_else(|| gentle_unwrap(t_data_file()))
             .into();
         web::Options {
             static_root,
             t_data_file,
             time_source: web::TimeSource::new(TIME_SOURCE.clone()),
+            calendar: calendar(),
         }
     }
 }
@@ -268,6 +527,14 @@ impl TryInto<sync::Options> for SyncArgs {
     }
 }
 
+#[derive(Options)]
+struct BackupsArgs {
+    #[options(help = "restore the backup with this number from the list")]
+    restore: Option<usize>,
+    #[options(help = "show this message")]
+    help: bool,
+}
+
 #[derive(Options)]
 struct NoArgs {
     #[options(help = "show this message")]
@@ -276,29 +543,83 @@ struct NoArgs {
 
 static TIME_SOURCE: DefaultTimeSource = DefaultTimeSource;
 
+static CALENDAR: OnceLock<Calendar> = OnceLock::new();
+
+// The config and calendar are set up once in main. The global options override the environment,
+// which overrides config.toml.
+fn config() -> &'static Config {
+    gentle_unwrap(Config::current())
+}
+
+fn calendar() -> Calendar {
+    *CALENDAR.get().expect("calendar is not set up")
+}
+
+fn set_up_config(opts: &MainOptions) {
+    let mut config = gentle_unwrap(Config::load());
+    config.data_file_arg = opts.data_file.as_ref().map(Into::into);
+    config.weekly_goal = opts.goal.or(config.weekly_goal);
+    config.sparks = opts.sparks.clone().or(config.sparks);
+    config.day_legend = opts.day_legend.clone().or(config.day_legend);
+    config.week_legend = opts.week_legend.clone().or(config.week_legend);
+    if let Some(goal) = opts.goal.filter(|&goal| goal <= 0) {
+        eprintln!("--goal must be more than 0 minutes, not {goal}");
+        std::process::exit(1);
+    }
+    gentle_unwrap(zone::set_up_local(config.time_zone.as_deref()));
+    let mut cal = gentle_unwrap(config.calendar());
+    if let Some(WeekdayArg(week_start)) = opts.week_start {
+        cal.week_start = week_start;
+    }
+    config.set_up();
+    CALENDAR.set(cal).expect("calendar is already set up");
+}
+
+fn sparks() -> Vec<char> {
+    match &config().sparks {
+        Some(sparks) if !sparks.is_empty() => sparks.chars().collect(),
+        _ => DEFAULT_SPARKS.to_vec(),
+    }
+}
+
 fn main() {
     let opts = MainOptions::parse_args_default_or_exit();
+    set_up_config(&opts);
     match opts.command {
         None => usage(),
         Some(cmd) => match cmd {
-            TCommand::Start(_) => cmd_start(),
-            TCommand::Stop(_) => cmd_stop(),
+            TCommand::Start(args) => cmd_start(args),
+            TCommand::Stop(args) => cmd_stop(args),
+            TCommand::Switch(args) => cmd_switch(args),
+            TCommand::Pause(args) => cmd_pause(args),
+            TCommand::Resume(args) => cmd_resume(args),
+            TCommand::Note(args) => cmd_note(args),
+            TCommand::Add(args) => cmd_add(args),
+            TCommand::Undo(_) => cmd_undo(),
+            TCommand::Redo(_) => cmd_redo(),
             TCommand::Edit(_) => cmd_edit(),
+            TCommand::Backups(args) => cmd_backups(args),
             TCommand::Status(args) => cmd_status(args),
             TCommand::List(args) => cmd_list(args),
+            TCommand::Breaks(args) => cmd_breaks(args),
             TCommand::Bitbar(args) => cmd_bitbar(args),
             TCommand::Today(_) => cmd_today(),
             TCommand::Week(_) => cmd_week(),
             TCommand::Race(args) => cmd_race(args),
-            TCommand::All(_) => cmd_all(),
+            TCommand::All(args) => cmd_all(args),
             //TCommand::Punchcard(_) => cmd_punchcard(),
             TCommand::Days(args) => cmd_days(args),
             TCommand::Csv(args) => cmd_csv(args),
+            TCommand::Export(args) => cmd_export(args),
             //TCommand::SVG(_) => cmd_svg(),
             TCommand::Pto(args) => cmd_pto(args),
             //TCommand::Short(_) => cmd_short(),
             TCommand::Path(_) => cmd_path(),
             TCommand::Validate(args) => cmd_validate(args),
+            TCommand::Fmt(args) => cmd_fmt(args),
+            TCommand::Repair(args) => cmd_repair(args),
+            TCommand::Archive(args) => cmd_archive(args),
+            TCommand::Import(args) => cmd_import(args),
             TCommand::Now(_) => cmd_now(),
             TCommand::Notes(_) => cmd_notes(),
             TCommand::Web(args) => web::main(args.into()),
@@ -318,13 +639,14 @@ fn gentle_unwrap<T, E: Display>(r: Result<T, E>) -> T {
 }
 
 fn usage() -> ! {
-    eprintln!("A command (start, stop, edit) or query (status, today, week, all, punchcard, days, csv, svg, pto, short, path) is required.");
+    eprintln!("A command (start, stop, switch, pause, resume, undo, redo, edit, backups, fmt, repair, archive, import) or query (status, today, week, all, punchcard, days, csv, export, svg, pto, short, path) is required.");
     std::process::exit(1)
 }
 
-fn cmd_start() {
+fn cmd_start(args: StartArgs) {
     cmd_validate(Default::default());
-    match start_new_entry(&TIME_SOURCE).unwrap() {
+    let at = args.at.map(|at| at.resolve());
+    match gentle_unwrap(start_new_entry(args.label, at, &TIME_SOURCE)) {
         None => println!("Starting work."),
         Some(minutes) => println!(
             "WARNING!!! You already started working, {} minutes ago!",
@@ -333,24 +655,152 @@ fn cmd_start() {
     };
 }
 
-fn cmd_stop() {
+fn cmd_stop(args: StopArgs) {
     cmd_validate(Default::default());
-    match stop_current_entry(&TIME_SOURCE).unwrap() {
+    let at = args.at.map(|at| at.resolve());
+    match gentle_unwrap(stop_current_entry(args.message, at, &TIME_SOURCE)) {
         Some((true, minutes)) => println!("You just worked for {} minutes.", minutes),
         Some((false, minutes)) => println!("You stopped {} minutes ago.", minutes),
         None => println!("You haven't started working yet!"),
     };
 }
 
+fn cmd_switch(args: StartArgs) {
+    cmd_validate(Default::default());
+    let at = args.at.map(|at| at.resolve());
+    let label = args.label;
+    let desc = label.as_deref().unwrap_or("(no label)").to_string();
+    match gentle_unwrap(switch_entry(label, at, &TIME_SOURCE)) {
+        Some(minutes) => println!("You worked for {} minutes. Switching to {}.", minutes, desc),
+        None => println!("Starting work on {}.", desc),
+    };
+}
+
+fn cmd_pause(args: PauseArgs) {
+    cmd_validate(Default::default());
+    let at = args.at.map(|at| at.resolve());
+    match gentle_unwrap(stop_current_entry(None, at, &TIME_SOURCE)) {
+        Some((true, minutes)) => println!(
+            "Pausing after {} minutes. Use 't resume' to continue.",
+            minutes
+        ),
+        Some((false, minutes)) => println!("You stopped {} minutes ago.", minutes),
+        None => println!("You haven't started working yet!"),
+    };
+}
+
+fn cmd_resume(args: PauseArgs) {
+    cmd_validate(Default::default());
+    let at = args.at.map(|at| at.resolve());
+    match gentle_unwrap(resume_last_entry(at, &TIME_SOURCE)) {
+        None => println!("Resuming work."),
+        Some(minutes) => println!(
+            "WARNING!!! You already started working, {} minutes ago!",
+            minutes
+        ),
+    };
+}
+
+fn cmd_note(args: NoteArgs) {
+    let text = args.text.join(" ");
+    if text.trim().is_empty() {
+        eprintln!("note text is required");
+        std::process::exit(1);
+    }
+    cmd_validate(Default::default());
+    match gentle_unwrap(add_note(text, &TIME_SOURCE)) {
+        Some(entry) => print!("Added a note to {}", entry),
+        None => println!("There isn't a time entry at the end of t.csv to attach a note to."),
+    };
+}
+
+fn cmd_add(args: AddArgs) {
+    let (date, start, stop) = match (args.date, args.start, args.stop) {
+        (Some(date), Some(start), Some(stop)) => (date.date, start, stop),
+        _ => {
+            eprintln!("usage: t add YYYY-MM-DD HH:MM HH:MM");
+            std::process::exit(1);
+        }
+    };
+    cmd_validate(Default::default());
+    let entry = TimeEntry::start_at(start.resolve_on(date))
+        .finish_at(stop.resolve_on(date))
+        .with_label(args.label)
+        .with_note(args.message);
+    gentle_unwrap(insert_entry(entry.clone(), &TIME_SOURCE));
+    print!("Added {}", entry);
+}
+
+fn cmd_undo() {
+    let summary = gentle_unwrap(undo_change(&TIME_SOURCE));
+    show_change_summary(summary, "Nothing to undo.");
+}
+
+fn cmd_redo() {
+    let summary = gentle_unwrap(redo_change(&TIME_SOURCE));
+    show_change_summary(summary, "Nothing to redo.");
+}
+
+fn show_change_summary(summary: Option<ChangeSummary>, nothing: &str) {
+    match summary {
+        None => println!("{nothing}"),
+        Some(summary) if summary.restored.is_empty() => {
+            for entry in summary.removed {
+                print!("Removed {}", entry);
+            }
+        }
+        Some(summary) => {
+            for entry in summary.restored {
+                print!("Restored {}", entry);
+            }
+        }
+    }
+}
+
+fn cmd_backups(args: BackupsArgs) {
+    let path = gentle_unwrap(t_data_file());
+    let backups = gentle_unwrap(backup::list(&path));
+    match args.restore {
+        None if backups.is_empty() => println!("There aren't any backups of {path}."),
+        None => {
+            for (n, backup) in backups.iter().enumerate() {
+                let taken = backup
+                    .taken
+                    .to_offset(TIME_SOURCE.zone().offset_of(backup.taken));
+                println!(
+                    "{:>3}  {}  {}",
+                    n + 1,
+                    taken.format("%Y-%m-%d %H:%M:%S %z"),
+                    backup.path.display()
+                );
+            }
+        }
+        Some(n) => match n.checked_sub(1).and_then(|i| backups.get(i)) {
+            None => {
+                eprintln!("there isn't a backup number {n}, see 't backups'");
+                std::process::exit(1);
+            }
+            Some(backup) => {
+                gentle_unwrap(restore_backup(backup));
+                println!("Restored {}.", backup.path.display());
+            }
+        },
+    }
+}
+
 fn cmd_edit() -> ! {
     let editor = std::env::var("EDITOR").unwrap();
     let path = t_data_file().unwrap();
-    let t = std::env::current_exe().unwrap();
 
-    // If we're using a vi-like editor, tell it to jump to the end of the file.
+    // If we're using a vi-like editor, tell it to jump to the first syntax error, or to the end
+    // of the file.
     let args = if editor.split("/").last().unwrap().contains("vi") {
-        match File::open(&path) {
-            Ok(f) => {
+        let first_error = read_live_entries_recovering(&TIME_SOURCE)
+            .ok()
+            .and_then(|parsed| parsed.errors.first().and_then(ParseError::position));
+        match (first_error, File::open(&path)) {
+            (Some((line, _)), _) => format!("+{line}"),
+            (None, Ok(f)) => {
                 let line_count = BufReader::new(f).lines().count();
                 format!("+{line_count}")
             }
@@ -360,17 +810,23 @@ fn cmd_edit() -> ! {
         "".to_owned()
     };
 
-    let cmd = format!("echo Editing with {editor}...; {editor} {args} \"$@\"; echo Validating {path}...; {t:?} validate --loud");
-    eprintln!(
-        "error: {}",
-        std::process::Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
-            .arg(editor)
-            .arg(path)
-            .exec()
-    );
-    std::process::exit(1)
+    // Hold the lock until the editor exits, so that nothing else changes the file underneath it,
+    // or has its change overwritten when the file is saved.
+    let lock = gentle_unwrap(lock::lock_exclusive(&path));
+    let cmd = format!("echo Editing with {editor}...; {editor} {args} \"$@\"");
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .arg(&editor)
+        .arg(&path)
+        .status();
+    drop(lock);
+    if let Err(e) = status {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+    println!("Validating {path}...");
+    std::process::exit(if do_validate(false, true) { 0 } else { 1 })
 }
 
 fn cmd_status(args: StatusArgs) {
@@ -379,9 +835,7 @@ fn cmd_status(args: StatusArgs) {
 }
 
 fn show_status(ui: impl StatusUI) -> bool {
-    let entries = query::for_cli(TIME_SOURCE.clone())
-        .tail()
-        .expect("error parsing data file");
+    let entries = gentle_unwrap(query::for_cli(TIME_SOURCE.clone()).this_week(&calendar()));
     println!("{}", ui.format(&entries));
     entries.is_working()
 }
@@ -402,7 +856,7 @@ impl StatusUI for CLIStatusUI {
             "NOT working"
         };
         if self.with_week {
-            let minutes = entries.minutes_between(extents::this_week());
+            let minutes = entries.minutes_between(extents::this_week(&calendar()));
             format!("{status_str} ({minutes})")
         } else {
             status_str.to_string()
@@ -419,35 +873,121 @@ impl From<StatusArgs> for CLIStatusUI {
 }
 
 fn cmd_list(args: ListArgs) {
+    let cal = calendar();
+    println!("{}", list_entries(listed_entries(args, &cal), &cal));
+}
+
+fn listed_entries(args: ListArgs, cal: &Calendar) -> Vec<TimeEntry> {
+    let (start, stop) = listed_range(args, cal);
+    let entries = read_time_entries_or_warn();
+    query::entries_between(&entries, start, stop)
+}
+
+fn listed_range(args: ListArgs, cal: &Calendar) -> (OffsetDateTime, OffsetDateTime) {
     let ListArgs {
         start,
         stop,
         help: _,
     } = args;
 
-    let now = OffsetDateTime::now_local();
+    let now = TIME_SOURCE.now();
+    let zone = TIME_SOURCE.zone();
 
-    let start = start.map(|s| s.date).unwrap_or_else(|| now.date());
+    let start = start.map(|s| s.date).unwrap_or_else(|| cal.workday(now));
     let stop = stop.map(|s| s.date).unwrap_or(start + Duration::day());
 
-    let entries = read_time_entries(&TIME_SOURCE).unwrap();
-    let entries = query::entries_between(
-        &entries,
-        start.midnight().assume_offset(now.offset()),
-        stop.midnight().assume_offset(now.offset()),
-    );
+    (
+        cal.start_of_day(start, &zone),
+        cal.start_of_day(stop, &zone),
+    )
+}
+
+fn cmd_export(args: ExportArgs) {
+    let format = match args.format {
+        Some(format) => format,
+        None => {
+            eprintln!("usage: t export ics|json|ndjson|timeclock [START [END]]");
+            std::process::exit(1);
+        }
+    };
+    let Parsed { entries, errors } = gentle_unwrap(read_entries_recovering(&TIME_SOURCE));
+    warn_about_parse_errors(errors);
+    let entries = match args.start {
+        None => entries.into_iter().enumerate().collect(),
+        start => {
+            let range = ListArgs {
+                start,
+                stop: args.stop,
+                help: false,
+            };
+            let (start, stop) = listed_range(range, &calendar());
+            export::between(entries, start, stop)
+        }
+    };
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    gentle_unwrap(match format {
+        export::Format::Ics => {
+            let entries = entries.into_iter().map(|(_, entry)| entry).collect();
+            export::write_ics(&mut out, &into_time_entries(entries)).map_err(Into::into)
+        }
+        export::Format::Json => export::write_json(&mut out, &entries),
+        export::Format::Ndjson => export::write_ndjson(&mut out, &entries),
+        export::Format::Timeclock => {
+            let entries = entries.into_iter().map(|(_, entry)| entry).collect();
+            export::write_timeclock(&mut out, &into_time_entries(entries)).map_err(Into::into)
+        }
+    });
+}
 
-    println!("{}", list_entries(entries));
+fn cmd_breaks(args: ListArgs) {
+    let cal = calendar();
+    let sessions = report::breaks::calc(listed_entries(args, &cal), &cal);
+    if sessions.is_empty() {
+        println!("\n(no entries)");
+        return;
+    }
+    let mut cur_date = None;
+    for session in sessions {
+        let sd = cal.workday(session.start().datetime());
+        if Some(sd) != cur_date {
+            println!("\n{sd}:");
+            cur_date = Some(sd);
+        }
+        print!("  {}", session.start().time());
+        match session.stop() {
+            None => print!(" - (still working)"),
+            Some(t) => print!(" - {}", t.time()),
+        };
+        if let Some(label) = session.label() {
+            print!(" [{label}]");
+        }
+        let breaks = session.breaks();
+        let break_minutes: i64 = breaks.iter().map(|b| b.minutes()).sum();
+        println!(
+            "  {} min worked, {} min of breaks",
+            session.minutes(&TIME_SOURCE),
+            break_minutes
+        );
+        for b in breaks {
+            println!(
+                "    break {} - {} ({} min)",
+                b.start.time(),
+                b.stop.time(),
+                b.minutes()
+            );
+        }
+    }
 }
 
-fn list_entries(entries: Vec<TimeEntry>) -> String {
+fn list_entries(entries: Vec<TimeEntry>, cal: &Calendar) -> String {
     if entries.is_empty() {
         return "\n(no entries)".into();
     }
     let mut cur_date = None;
     let mut res = "".to_string();
     for e in entries {
-        let ed = e.start.date();
+        let ed = cal.workday(e.start.datetime());
         if Some(ed) != cur_date {
             res.push_str(&format!("\n\n{ed}:"));
             cur_date = Some(ed);
@@ -457,6 +997,15 @@ fn list_entries(entries: Vec<TimeEntry>) -> String {
             None => res.push_str(" - (still working)"),
             Some(t) => res.push_str(&format!(" - {}", t.time())),
         };
+        if let Some(label) = e.label {
+            res.push_str(&format!(" [{label}]"));
+        }
+        if e.resumed {
+            res.push_str(" (resumed)");
+        }
+        for note in e.notes {
+            res.push_str(&format!("\n    # {note}"));
+        }
     }
     res
 }
@@ -464,8 +1013,8 @@ fn list_entries(entries: Vec<TimeEntry>) -> String {
 fn cmd_bitbar(args: BitBarArgs) {
     match args.command.as_str() {
         "" => show_bitbar_plugin(&args.wrapper),
-        "start" => cmd_start(),
-        "stop" => cmd_stop(),
+        "start" => cmd_start(Default::default()),
+        "stop" => cmd_stop(Default::default()),
         _ => panic!("unrecognized command: {}", args.command),
     };
 }
@@ -496,10 +1045,13 @@ fn show_bitbar_plugin(mut wrapper: &str) {
 }
 
 fn show_entry() {
-    let entries = read_last_entries(1, &TIME_SOURCE).expect("error parsing data file");
+    let entries = read_last_entries_or_warn(1);
     match entries.last() {
         Some(Entry::Time(te)) if !te.is_finished() => {
-            println!("Working for {} minutes.", te.minutes(&TIME_SOURCE))
+            println!("Working for {} minutes.", te.minutes(&TIME_SOURCE));
+            for note in &te.notes {
+                println!("{note}");
+            }
         }
         Some(Entry::Note(n)) => println!("{n}"),
         _ => println!("Not working."),
@@ -511,7 +1063,7 @@ struct BitBarStatusUI;
 impl StatusUI for BitBarStatusUI {
     fn format(&self, entries: &EntriesResult) -> String {
         let status_str = if entries.is_working() { "👔" } else { "😴" };
-        let minutes = entries.minutes_between(extents::this_week());
+        let minutes = entries.minutes_between(extents::this_week(&calendar()));
         format!("{status_str}{}", week_progress_emoji(minutes))
     }
 }
@@ -522,10 +1074,8 @@ fn cmd_today() {
 }
 
 fn show_today() {
-    let (start_today, now) = extents::today();
-    // longest week so far is 46 entries, so 100 should be totally fine for a day.
-    let entries = read_last_entries(100, &TIME_SOURCE).expect("error parsing data file");
-    let entries = into_time_entries(entries);
+    let (start_today, now) = extents::today(&calendar());
+    let entries = into_time_entries(read_entries_since_or_warn(start_today));
     let minutes = minutes_between(&entries, start_today, now);
     println!("You have worked for {} minutes today.", minutes);
 }
@@ -536,10 +1086,8 @@ fn cmd_week() {
 }
 
 fn show_week() {
-    let (start_week, now) = extents::this_week();
-    // longest week so far is 46 entries, so 100 should be totally fine.
-    let entries = read_last_entries(100, &TIME_SOURCE).expect("error parsing data file");
-    let entries = into_time_entries(entries);
+    let (start_week, now) = extents::this_week(&calendar());
+    let entries = into_time_entries(read_entries_since_or_warn(start_week));
     let minutes = minutes_between(&entries, start_week, now);
     println!(
         "You have worked for {} minutes since {}.",
@@ -554,10 +1102,9 @@ fn cmd_race(args: RaceArgs) {
 }
 
 fn show_race(previous_weeks: i16, suffix: &str) {
-    let res = query::for_cli(TIME_SOURCE.clone())
-        .all()
-        .expect("error parsing data file");
-    let (start_week, now) = extents::this_week();
+    let res =
+        gentle_unwrap(query::for_cli(TIME_SOURCE.clone()).recent(previous_weeks, &calendar()));
+    let (start_week, now) = extents::this_week(&calendar());
     let minutes_this_week = res.minutes_between((start_week, now));
 
     println!(
@@ -572,7 +1119,7 @@ fn show_race(previous_weeks: i16, suffix: &str) {
     let mut total_prev_minutes = 0;
     let mut behind = 0;
     let mut ahead = 0;
-    for w in res.recent_weeks(previous_weeks).iter().rev() {
+    for w in res.recent_weeks(previous_weeks, &calendar()).iter().rev() {
         let minutes = w.minutes_to_date();
         println!(
             "{}: {} {:4} minutes {}{}",
@@ -613,9 +1160,30 @@ fn race_bars(n: i64) -> String {
     }
 }
 
-fn cmd_all() {
-    let entries = read_time_entries(&TIME_SOURCE).expect("error parsing data file");
-    for line in report::all::calc(entries, &DEFAULT_SPARKS, &TIME_SOURCE) {
+fn cmd_all(args: AllArgs) {
+    if args.label.is_none() && !args.by_label {
+        let weeks = read_weekly_totals_or_warn();
+        show_all(report::all::calc_from_totals(weeks, &sparks()));
+        print_week_legend();
+        return;
+    }
+    let entries = read_time_entries_or_warn();
+    for (label, entries) in select_by_label(entries, args.label, args.by_label) {
+        if args.by_label {
+            println!("{}", label_heading(&label));
+        }
+        show_all(report::all::calc(
+            entries,
+            &sparks(),
+            &calendar(),
+            &TIME_SOURCE,
+        ));
+    }
+    print_week_legend();
+}
+
+fn show_all(lines: Vec<report::all::All<char>>) {
+    for line in lines {
         let week_end = line.start + Duration::days(6);
         print!("{} - {}   {:4} min", line.start, week_end, line.minutes);
         if let Some(analysis) = line.analysis {
@@ -623,6 +1191,9 @@ fn cmd_all() {
                 " {:4} segments  min/avg/max/stddev={:3}/{:3}/{:3}/{:3}  ",
                 line.segments, analysis.min, analysis.mean, analysis.max, analysis.stddev
             );
+            if line.pauses > 0 {
+                print!("{} pauses  ", line.pauses);
+            }
             let mut first = true;
             for day in analysis.sparks {
                 if !day.is_empty() {
@@ -638,7 +1209,6 @@ fn cmd_all() {
         }
         println!();
     }
-    print_week_legend();
 }
 
 /*
@@ -649,84 +1219,190 @@ let width = match term_size::dimensions() {
 */
 
 fn cmd_days(args: DaysArgs) {
+    let label = args.label.clone();
+    let by_label = args.by_label;
     let (filters, opts) = args.into();
 
-    let entries = read_time_entries(&TIME_SOURCE).expect("error parsing data file");
+    if filters.is_empty() && label.is_none() && !by_label {
+        let weeks = read_weekly_totals_or_warn();
+        print!("{}", report::days::prepare_from_totals(weeks, opts));
+        print_week_legend();
+        return;
+    }
+    let entries = read_time_entries_or_warn();
     let entries = filter_entries(entries, filters).expect("unusable filter");
 
-    print!("{}", report::days::prepare(entries, &TIME_SOURCE, opts));
+    for (label, entries) in select_by_label(entries, label, by_label) {
+        if by_label {
+            println!("{}", label_heading(&label));
+        }
+        print!(
+            "{}",
+            report::days::prepare(entries, &calendar(), &TIME_SOURCE, opts)
+        );
+    }
     print_week_legend();
 }
 
 fn cmd_csv(args: CSVArgs) {
-    match args.report_type {
+    let CSVArgs {
+        report_type,
+        label,
+        by_label,
+        help: _,
+    } = args;
+    let label_col = if by_label { "label," } else { "" };
+    match report_type {
         None => eprintln!("report type is required"),
         Some(ReportType::Weekly) => {
-            let entries = read_time_entries(&TIME_SOURCE).expect("error parsing data file");
-            println!("start of week,minutes");
-            for line in report::all::calc(entries, &DEFAULT_SPARKS, &TIME_SOURCE) {
-                println!("{},{}", line.start, line.minutes);
+            let entries = read_time_entries_or_warn();
+            println!("{label_col}start of week,minutes");
+            for (label, entries) in select_by_label(entries, label, by_label) {
+                let label_val = csv_label(&label, by_label);
+                for line in report::all::calc(entries, &sparks(), &calendar(), &TIME_SOURCE) {
+                    println!("{label_val}{},{}", line.start, line.minutes);
+                }
             }
         }
         Some(ReportType::YearVsYear) => {
-            let entries = read_time_entries(&TIME_SOURCE).expect("error parsing data file");
-            let mut years = Vec::new();
-            struct Year {
-                year: i32,
-                weeks: HashMap<u8, i64>,
-            }
-            let mut cur_year: Option<Year> = None;
-            for line in report::all::calc(entries, &DEFAULT_SPARKS, &TIME_SOURCE) {
-                let mut yy = match (line.start.year(), cur_year) {
-                    (y, Some(yy)) if y == yy.year => yy,
-                    (_, None) => Year {
-                        year: line.start.year(),
-                        weeks: HashMap::new(),
-                    },
-                    (_, Some(yy)) => {
-                        years.push(yy);
-                        Year {
-                            year: line.start.year(),
-                            weeks: HashMap::new(),
-                        }
-                    }
-                };
-                yy.weeks.insert(line.start.week(), line.minutes);
-                cur_year = Some(yy);
-            }
-            if let Some(yy) = cur_year.take() {
-                years.push(yy);
-            }
-            print!("week of year");
-            for yy in &years {
-                print!(",{}", yy.year);
+            let entries = read_time_entries_or_warn();
+            let groups: Vec<(Option<String>, WeeksByYear)> =
+                select_by_label(entries, label, by_label)
+                    .into_iter()
+                    .map(|(label, entries)| (label, year_vs_year(entries)))
+                    .collect();
+            let years: BTreeSet<i32> = groups
+                .iter()
+                .flat_map(|(_, years)| years.keys().copied())
+                .collect();
+            print!("{label_col}week of year");
+            for year in &years {
+                print!(",{}", year);
             }
             println!();
-            for week_num in 1..=53 {
-                print!("{week_num}");
-                for yy in &years {
-                    match yy.weeks.get(&week_num) {
-                        None => print!(","),
-                        Some(min) => print!(",{min}"),
-                    };
+            for (label, weeks_by_year) in &groups {
+                let label_val = csv_label(label, by_label);
+                for week_num in 1..=53 {
+                    print!("{label_val}{week_num}");
+                    for year in &years {
+                        match weeks_by_year
+                            .get(year)
+                            .and_then(|weeks| weeks.get(&week_num))
+                        {
+                            None => print!(","),
+                            Some(min) => print!(",{min}"),
+                        };
+                    }
+                    println!();
                 }
-                println!();
             }
         }
     };
 }
 
+type WeeksByYear = BTreeMap<i32, HashMap<u8, i64>>;
+
+fn year_vs_year(entries: Vec<TimeEntry>) -> WeeksByYear {
+    let mut years = WeeksByYear::new();
+    for line in report::all::calc(entries, &sparks(), &calendar(), &TIME_SOURCE) {
+        years
+            .entry(line.start.year())
+            .or_default()
+            .insert(line.start.week(), line.minutes);
+    }
+    years
+}
+
+fn csv_label(label: &Option<String>, by_label: bool) -> String {
+    match (by_label, label) {
+        (false, _) => "".to_string(),
+        (true, None) => ",".to_string(),
+        (true, Some(label)) => format!("{label},"),
+    }
+}
+
 fn cmd_pto(args: PtoArgs) {
-    let entries = read_time_entries(&TIME_SOURCE).expect("error parsing data file");
-    let full_week = args.full_week.unwrap_or(FULL_WEEK);
-    print!("{}", report::pto::prepare(entries, full_week, &TIME_SOURCE));
+    let entries = read_time_entries_or_warn();
+    let full_week = args.full_week.or(config().full_week).unwrap_or(FULL_WEEK);
+    for (label, entries) in select_by_label(entries, args.label, args.by_label) {
+        if args.by_label {
+            println!("{}", label_heading(&label));
+        }
+        print!(
+            "{}",
+            report::pto::prepare(entries, full_week, &calendar(), &TIME_SOURCE)
+        );
+    }
     print_week_legend();
 }
 
+/// Applies the --label and --by-label options that the reports share. Without --by-label, there
+/// is a single group.
+fn select_by_label(
+    entries: Vec<TimeEntry>,
+    label: Option<String>,
+    by_label: bool,
+) -> Vec<(Option<String>, Vec<TimeEntry>)> {
+    let entries = match label {
+        None => entries,
+        Some(label) => filter_by_label(entries, &label),
+    };
+    if by_label {
+        group_by_label(entries).into_iter().collect()
+    } else {
+        vec![(None, entries)]
+    }
+}
+
+fn label_heading(label: &Option<String>) -> String {
+    match label {
+        None => "== (no label) ==".to_string(),
+        Some(label) => format!("== {label} =="),
+    }
+}
+
 fn cmd_path() {
     println!("{}", t_data_file().unwrap());
 }
 
+// Reports skip the lines that can't be parsed, after warning about them. `t validate` lists them
+// too.
+fn read_entries_or_warn() -> Vec<Entry> {
+    let Parsed { entries, errors } = gentle_unwrap(read_entries_recovering(&TIME_SOURCE));
+    warn_about_parse_errors(errors);
+    entries
+}
+
+fn read_time_entries_or_warn() -> Vec<TimeEntry> {
+    into_time_entries(read_entries_or_warn())
+}
+
+// The weekly totals come from the cache, so only the newest entries are parsed and warned about.
+fn read_weekly_totals_or_warn() -> Vec<WeekTotals> {
+    let Totals { weeks, errors } = gentle_unwrap(read_weekly_totals(&calendar(), &TIME_SOURCE));
+    warn_about_parse_errors(errors);
+    weeks
+}
+
+fn read_last_entries_or_warn(n: u64) -> Vec<Entry> {
+    let Parsed { entries, errors } = gentle_unwrap(read_last_entries_recovering(n, &TIME_SOURCE));
+    warn_about_parse_errors(errors);
+    entries
+}
+
+fn read_entries_since_or_warn(since: OffsetDateTime) -> Vec<Entry> {
+    let Parsed { entries, errors } =
+        gentle_unwrap(read_entries_since_recovering(since, &TIME_SOURCE));
+    warn_about_parse_errors(errors);
+    entries
+}
+
+fn warn_about_parse_errors(errors: Vec<ParseError>) {
+    for err in errors {
+        eprintln!("warning: skipped {err}");
+    }
+}
+
 fn cmd_validate(args: ValidateArgs) {
     let ValidateArgs {
         count,
@@ -750,7 +1426,12 @@ fn do_validate(verbose: bool, loud: bool) -> bool {
     let mut count = 0;
     let mut state = Validation::Good;
     let prefix = if loud { "!!! " } else { "" };
-    for (n, entry) in read_entries(&TIME_SOURCE).unwrap().into_iter().enumerate() {
+    let Parsed { entries, errors } = gentle_unwrap(read_live_entries_recovering(&TIME_SOURCE));
+    for err in errors {
+        state = state.bad(loud);
+        println!("{prefix}{err}");
+    }
+    for (n, entry) in entries.into_iter().enumerate() {
         if !last_entry_is_finished {
             state = state.bad(loud);
             println!("{prefix}{n}: previous entry is not finished");
@@ -771,12 +1452,149 @@ fn do_validate(verbose: bool, loud: bool) -> bool {
     state.ok()
 }
 
+fn cmd_fmt(args: FmtArgs) {
+    let path = gentle_unwrap(t_data_file());
+    let summary = gentle_unwrap(format_file(args.explicit_tz, args.check, &TIME_SOURCE));
+    if summary.is_empty() {
+        println!("{path} is already formatted.");
+        return;
+    }
+    let (reformat, remove) = if args.check {
+        ("would reformat", "remove")
+    } else {
+        ("reformatted", "removed")
+    };
+    let mut changes = vec![];
+    if summary.changed > 0 {
+        changes.push(format!(
+            "{reformat} {} {}",
+            summary.changed,
+            lines(summary.changed)
+        ));
+    }
+    if summary.blank > 0 {
+        let blank = format!("{} blank {}", summary.blank, lines(summary.blank));
+        changes.push(match changes.is_empty() {
+            true if args.check => format!("would {remove} {blank}"),
+            _ => format!("{remove} {blank}"),
+        });
+    }
+    if args.check {
+        println!("{path} isn't formatted, 't fmt' {}.", changes.join(" and "));
+        std::process::exit(1);
+    }
+    println!("Formatted {path}: {}.", changes.join(" and "));
+}
+
+fn lines(n: usize) -> &'static str {
+    if n == 1 {
+        "line"
+    } else {
+        "lines"
+    }
+}
+
+fn cmd_archive(args: ArchiveArgs) {
+    let archived = gentle_unwrap(archive_entries(args.before, &TIME_SOURCE));
+    if archived.is_empty() {
+        println!("Nothing to archive from before {}.", args.before);
+    }
+    for (path, count) in archived {
+        let entries = if count == 1 { "entry" } else { "entries" };
+        println!("Moved {count} {entries} to {}.", path.display());
+    }
+}
+
+fn cmd_import(args: ImportArgs) {
+    let (path, format) = match (args.file, args.from) {
+        (Some(path), Some(format)) => (path, format),
+        _ => {
+            eprintln!("usage: t import --from timewarrior|toggl-csv|json|ndjson|timeclock FILE");
+            std::process::exit(1);
+        }
+    };
+    let f = gentle_unwrap(File::open(&path).map_err(|e| format!("{path}: {e}")));
+    let foreign = gentle_unwrap(import::read(f, format, &TIME_SOURCE.zone()));
+    for problem in &foreign.skipped {
+        eprintln!("warning: skipped {problem}");
+    }
+    let summary = gentle_unwrap(import_entries(foreign.entries, &TIME_SOURCE));
+    for (entry, reason) in &summary.rejected {
+        eprintln!(
+            "warning: didn't import the entry that starts at {}: {}",
+            entry.start, reason
+        );
+    }
+    let entries = if summary.added == 1 {
+        "entry"
+    } else {
+        "entries"
+    };
+    println!("Imported {} {entries} from {path}.", summary.added);
+    if !foreign.skipped.is_empty() || !summary.rejected.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn cmd_repair(args: RepairArgs) {
+    let repairs = gentle_unwrap(repair_file(
+        |fix| {
+            println!(
+                "The entry that starts at {} {}.",
+                fix.before.start, fix.problem
+            );
+            println!("  before: {}", first_line(&fix.before));
+            println!("  after:  {}", first_line(&fix.after));
+            args.yes || confirm("Fix it?")
+        },
+        &TIME_SOURCE,
+    ));
+    for problem in &repairs.unfixable {
+        println!("Can't fix this automatically, use 't edit': {problem}");
+    }
+    let (fixed, skipped) = (repairs.applied.len(), repairs.declined.len());
+    let problems = |n| if n == 1 { "problem" } else { "problems" };
+    match (fixed, skipped, repairs.unfixable.len()) {
+        (0, 0, 0) => println!("Nothing to repair."),
+        _ if skipped > 0 => println!(
+            "Fixed {fixed} of {} {}.",
+            fixed + skipped,
+            problems(fixed + skipped)
+        ),
+        _ => println!("Fixed {fixed} {}.", problems(fixed)),
+    }
+    if skipped > 0 || !repairs.unfixable.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+// Notes are left out, since repairs don't touch them.
+fn first_line(entry: &TimeEntry) -> String {
+    entry
+        .to_string()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn confirm(question: &str) -> bool {
+    print!("{question} [y/N] ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
 enum Validation {
     Good,
     Bad,
 }
 
 impl Validation {
+    #[allow(clippy::single_match, clippy::collapsible_match)]
     fn bad(self, loud: bool) -> Self {
         match self {
             Validation::Good => {
@@ -805,10 +1623,20 @@ fn cmd_now() {
 
 fn cmd_notes() {
     let mut last_time = None;
-    for entry in read_entries(&TIME_SOURCE).unwrap().into_iter() {
+    for entry in read_entries_or_warn() {
         match entry {
-            Entry::Note(s) => println!("{}: {}", last_time.as_ref().unwrap(), s.trim()),
-            Entry::Time(te) => last_time = Some(format!("{}", te.start)),
+            // Notes that aren't attached to an entry are shown with the entry before them.
+            Entry::Note(s) => match &last_time {
+                None => println!("{}", s.trim()),
+                Some(t) => println!("{}: {}", t, s.trim()),
+            },
+            Entry::Time(te) => {
+                let t = format!("{}", te.start);
+                for note in &te.notes {
+                    println!("{}: {}", t, note);
+                }
+                last_time = Some(t);
+            }
         };
     }
 }
@@ -820,14 +1648,15 @@ fn minutes_between(entries: &[TimeEntry], start: OffsetDateTime, stop: OffsetDat
 }
 
 fn week_progress_emoji(minutes: i64) -> char {
-    let fraction = HOUR_EMOJI.len() * minutes as usize / MY_FULL_WEEK as usize;
+    let goal = config().weekly_goal.unwrap_or(MY_FULL_WEEK);
+    let fraction = HOUR_EMOJI.len() * minutes as usize / goal as usize;
     HOUR_EMOJI.get(fraction).copied().unwrap_or(CHECK_EMOJI)
 }
 
 fn print_day_legend() {
-    println!("8h=480m");
+    println!("{}", config().day_legend.as_deref().unwrap_or(DAY_LEGEND));
 }
 
 fn print_week_legend() {
-    println!("8h=480m 16h=960m 24h=1440m 32h=1920m 40h=2400m");
+    println!("{}", config().week_legend.as_deref().unwrap_or(WEEK_LEGEND));
 }
@@ -1,29 +1,179 @@
-use crate::entry::{into_time_entries, Entry, Time, TimeEntry, TZ};
+use crate::entry::{into_time_entries, Entry, Time, TimeEntry, RESUME_MARKER, TZ};
 use crate::timesource::TimeSource;
+use crate::zone::Zone;
 use std::error::Error;
+use std::fmt;
 use std::io::{self, BufRead, BufReader, Read, Write};
 
+/// ParseError says what's wrong with a t.csv file and where. Lines and columns start at 1, and
+/// the column is where the problem was noticed.
 #[derive(Debug)]
-struct ParseError {
-    message: String,
+pub enum ParseError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The file ended in the middle of a time entry.
+    UnexpectedEof {
+        line: usize,
+        col: usize,
+        expected: String,
+    },
+    /// A character that doesn't belong, like a letter where a digit should be.
+    UnexpectedChar {
+        line: usize,
+        col: usize,
+        expected: String,
+        found: char,
+    },
+    /// A well-formed time that doesn't exist, like month 13 or 25:00. The column is where the
+    /// time starts.
+    InvalidTime {
+        line: usize,
+        col: usize,
+        text: String,
+        reason: String,
+    },
+    /// A note or label that isn't valid UTF-8. The column is where the first bad byte is.
+    InvalidUtf8 { line: usize, col: usize },
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        formatter.write_str(&self.message)
+impl ParseError {
+    /// The line and column of the problem, unless the file couldn't be read at all.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            ParseError::Io(_) => None,
+            ParseError::UnexpectedEof { line, col, .. }
+            | ParseError::UnexpectedChar { line, col, .. }
+            | ParseError::InvalidTime { line, col, .. }
+            | ParseError::InvalidUtf8 { line, col } => Some((*line, *col)),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, col)) = self.position() {
+            write!(f, "line {}, col {}: ", line, col)?;
+        }
+        match self {
+            ParseError::Io(e) => write!(f, "{}", e),
+            ParseError::UnexpectedEof { expected, .. } => {
+                write!(f, "expected {} but got EOF", expected)
+            }
+            ParseError::UnexpectedChar {
+                expected, found, ..
+            } => write!(f, "expected {} but got {:?}", expected, found),
+            ParseError::InvalidTime { text, reason, .. } => {
+                write!(f, "invalid time '{}': {}", text, reason)
+            }
+            ParseError::InvalidUtf8 { .. } => write!(f, "invalid UTF-8"),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+pub fn parse_entries<R: Read, TS: TimeSource>(r: R, ts: &TS) -> Result<Vec<Entry>, ParseError> {
+    entries(BufReader::new(r), ts).collect()
+}
+
+/// Iterate over the entries in a file without reading all of them into memory first.
+pub fn entries<R: BufRead, TS: TimeSource>(r: R, ts: &TS) -> Entries<R> {
+    Entries {
+        parser: Parser::new(r, ts.zone()),
+        skip_line: false,
+        done: false,
     }
 }
 
-impl Error for ParseError {}
+/// Entries parses one entry at a time. After a syntax error it moves on to the next line, so the
+/// caller can decide whether to stop or to keep going. It stops for good after an I/O error.
+pub struct Entries<R: BufRead> {
+    parser: Parser<R>,
+    skip_line: bool,
+    done: bool,
+}
+
+impl<R: BufRead> Entries<R> {
+    /// Where the entry that was returned last starts, in bytes from where the reader was when
+    /// the iterator was created.
+    pub fn entry_start(&self) -> u64 {
+        self.parser.entry_start
+    }
+
+    /// Only the time entries, without any of the notes between them.
+    pub fn time_entries(self) -> impl Iterator<Item = Result<TimeEntry, ParseError>> {
+        self.filter_map(|entry| match entry {
+            Ok(entry) => entry.try_into_time().map(Ok),
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
 
-pub fn parse_entries<R: Read, TS: TimeSource>(r: R, ts: &TS) -> Result<Vec<Entry>, Box<dyn Error>> {
-    let r = BufReader::new(r);
-    let mut parser = Parser::new(r, ts.local_offset());
-    let mut res = vec![];
-    loop {
-        match parser.parse_entry()? {
-            None => break,
-            Some(entry) => res.push(entry),
+impl<R: BufRead> Iterator for Entries<R> {
+    type Item = Result<Entry, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.skip_line {
+            self.skip_line = false;
+            if let Err(e) = self.parser.skip_rest_of_line() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        match self.parser.parse_entry() {
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Err(ParseError::Io(e)) => {
+                self.done = true;
+                Some(Err(ParseError::Io(e)))
+            }
+            Err(e) => {
+                self.skip_line = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Parsed is everything that could be parsed from a file, plus an error for each line that
+/// couldn't be.
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub entries: Vec<Entry>,
+    pub errors: Vec<ParseError>,
+}
+
+/// Parse as much as possible. A line with a syntax error is skipped, and the error is returned
+/// along with the entries from all of the other lines. I/O errors still stop the parse.
+pub fn parse_entries_recovering<R: Read, TS: TimeSource>(
+    r: R,
+    ts: &TS,
+) -> Result<Parsed, ParseError> {
+    let mut res = Parsed::default();
+    for entry in entries(BufReader::new(r), ts) {
+        match entry {
+            Ok(entry) => res.entries.push(entry),
+            Err(ParseError::Io(e)) => return Err(ParseError::Io(e)),
+            Err(e) => res.errors.push(e),
         }
     }
     Ok(res)
@@ -32,15 +182,15 @@ pub fn parse_entries<R: Read, TS: TimeSource>(r: R, ts: &TS) -> Result<Vec<Entry
 pub fn parse_time_entries<R: Read, TS: TimeSource>(
     r: R,
     ts: &TS,
-) -> Result<Vec<TimeEntry>, Box<dyn Error>> {
+) -> Result<Vec<TimeEntry>, ParseError> {
     Ok(into_time_entries(parse_entries(r, ts)?))
 }
 
 pub fn parse_entry<R: BufRead, TS: TimeSource>(
     r: R,
     ts: &TS,
-) -> Result<(Option<Entry>, R), Box<dyn Error>> {
-    let mut parser = Parser::new(r, ts.local_offset());
+) -> Result<(Option<Entry>, R), ParseError> {
+    let mut parser = Parser::new(r, ts.zone());
     let entry = parser.parse_entry()?;
     Ok((entry, parser.reader))
 }
@@ -54,9 +204,15 @@ pub fn write_entries(w: &mut impl Write, entries: &[Entry]) -> Result<(), Box<dy
 
 struct Parser<R: BufRead> {
     reader: R,
-    default_tz: time::UtcOffset,
+    default_tz: Zone,
     line: usize,
     col: usize,
+    /// Where the last byte that was read is, for error messages.
+    last: (usize, usize),
+    /// How many bytes have been read.
+    offset: u64,
+    /// The offset of the first byte of the entry that's being parsed.
+    entry_start: u64,
 }
 
 /// Morsel is either an annotation or the start of a time entry.
@@ -67,163 +223,263 @@ enum Morsel {
 }
 
 impl<R: BufRead> Parser<R> {
-    fn new(r: R, default_tz: time::UtcOffset) -> Parser<R> {
+    fn new(r: R, default_tz: Zone) -> Parser<R> {
         Parser {
             reader: r,
             default_tz,
             line: 1,
             col: 0,
+            last: (1, 0),
+            offset: 0,
+            entry_start: 0,
         }
     }
 
-    fn parse_entry(&mut self) -> Result<Option<Entry>, Box<dyn Error>> {
+    fn parse_entry(&mut self) -> Result<Option<Entry>, ParseError> {
         match self.parse_morsel()? {
             Morsel::None => Ok(None),
             Morsel::Note(note) => Ok(Some(Entry::Note(note))),
-            Morsel::Time(start, true) => Ok(Some(TimeEntry { start, stop: None }.into())),
-            Morsel::Time(start, false) => match (self.parse_time(None))? {
-                None => Ok(Some(TimeEntry { start, stop: None }.into())),
-                Some((stop, _)) => Ok(Some(
+            Morsel::Time(start, true) => {
+                let notes = self.parse_attached_notes()?;
+                Ok(Some(
                     TimeEntry {
                         start,
-                        stop: Some(stop),
+                        stop: None,
+                        label: None,
+                        notes,
+                        resumed: false,
                     }
                     .into(),
-                )),
-            },
+                ))
+            }
+            Morsel::Time(start, false) => {
+                let (stop, eol) = self.parse_stop()?;
+                let (label, resumed) = if eol {
+                    (None, false)
+                } else {
+                    self.parse_label_and_marker()?
+                };
+                let notes = self.parse_attached_notes()?;
+                Ok(Some(
+                    TimeEntry {
+                        start,
+                        stop,
+                        label,
+                        notes,
+                        resumed,
+                    }
+                    .into(),
+                ))
+            }
         }
     }
 
-    fn parse_morsel(&mut self) -> Result<Morsel, Box<dyn Error>> {
+    /// Notes that belong to a time entry are indented on the lines right after it. Anything else,
+    /// including a '#' line that isn't indented, is left for the next call to parse_morsel.
+    fn parse_attached_notes(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut notes = vec![];
         loop {
+            // Leading spaces are skipped by parse_morsel anyway, so it's fine to consume them here.
+            let mut indented = false;
+            while self.peek()? == Some(b' ') {
+                self.read()?;
+                indented = true;
+            }
+            if !indented || self.peek()? != Some(b'#') {
+                return Ok(notes);
+            }
+            self.read()?;
+            notes.push(self.read_line()?.trim().to_string());
+        }
+    }
+
+    fn parse_morsel(&mut self) -> Result<Morsel, ParseError> {
+        loop {
+            let offset = self.offset;
             match self.read()? {
                 None => return Ok(Morsel::None),
                 Some(b' ') | Some(b'\n') => (),
-                Some(b'#') => return Ok(Morsel::Note(self.read_line()?)),
+                Some(b'#') => {
+                    self.entry_start = offset;
+                    return Ok(Morsel::Note(self.read_line()?));
+                }
                 Some(digit) => {
-                    let digit = self.parse_digit(digit)?;
-                    let year = 1000 * digit + self.read_number(100)?;
-                    let (t, b) = self.parse_time(Some(year))?.unwrap();
+                    self.entry_start = offset;
+                    let (t, b) = self.parse_time(digit)?;
                     return Ok(Morsel::Time(t, b));
                 }
             }
         }
     }
 
-    fn parse_time(&mut self, year: Option<u16>) -> Result<Option<(Time, bool)>, Box<dyn Error>> {
-        let year = match year {
-            Some(y) => Some(y),
-            None => self.read_year()?,
-        };
-        match year {
-            None => Ok(None),
-            Some(year) => {
-                self.read_expected(b'-')?;
-                let month = self.read_number(10)? as u8;
-                self.read_expected(b'-')?;
-                let day = self.read_number(10)? as u8;
-                self.read_expected(b' ')?;
-                let hour = self.read_number(10)? as u8;
-                self.read_expected(b':')?;
-                let minute = self.read_number(10)? as u8;
+    /// Parses the rest of a time entry after the comma that follows its start time. The stop time
+    /// is optional, and the bool is true if the line ended instead of moving on to a label.
+    fn parse_stop(&mut self) -> Result<(Option<Time>, bool), ParseError> {
+        loop {
+            match self.read()? {
+                None | Some(b'\n') => return Ok((None, true)),
+                Some(b' ') => (),
+                Some(b',') => return Ok((None, false)),
+                Some(digit) => {
+                    let (t, b) = self.parse_time(digit)?;
+                    return Ok((Some(t), b));
+                }
+            }
+        }
+    }
+
+    fn parse_time(&mut self, first_digit: u8) -> Result<(Time, bool), ParseError> {
+        let at = self.last;
+        let year = self.parse_year(first_digit)?;
+        self.read_expected(b'-')?;
+        let month = self.read_number(10)? as u8;
+        self.read_expected(b'-')?;
+        let day = self.read_number(10)? as u8;
+        self.read_expected(b' ')?;
+        let hour = self.read_number(10)? as u8;
+        self.read_expected(b':')?;
+        let minute = self.read_number(10)? as u8;
+
+        match self.read()? {
+            // EOF or EOL.
+            None | Some(b'\n') => Ok((
+                self.new_time(at, year, month, day, hour, minute, self.implied_tz())?,
+                true,
+            )),
+            // End of current entry.
+            Some(b',') => Ok((
+                self.new_time(at, year, month, day, hour, minute, self.implied_tz())?,
+                false,
+            )),
+            // TZ follows the space.
+            Some(b' ') => {
+                let sign: i16 = match self.read()? {
+                    Some(b'-') => -1,
+                    Some(b'+') => 1,
+                    x => return Err(self.unexpected("+/-", x)),
+                };
+                let hr_off = self.read_number(10)? as i16;
+                let min_off = self.read_number(10)? as i16;
+
+                let total_min_off = sign * ((hr_off * 60) + min_off);
+                let tz = TZ::Known(time::UtcOffset::minutes(total_min_off));
+                let res = self.new_time(at, year, month, day, hour, minute, tz)?;
 
                 match self.read()? {
                     // EOF or EOL.
-                    None | Some(b'\n') => Ok(Some((
-                        Time::new(year, month, day, hour, minute, self.implied_tz())?,
-                        true,
-                    ))),
+                    None | Some(b'\n') => Ok((res, true)),
                     // End of current entry.
-                    Some(b',') => Ok(Some((
-                        Time::new(year, month, day, hour, minute, self.implied_tz())?,
-                        false,
-                    ))),
-                    // TZ follows the space.
-                    Some(b' ') => {
-                        let sign: i16 = match self.read()? {
-                            Some(b'-') => -1,
-                            Some(b'+') => 1,
-                            None => return Err(self.error("expected +/- but got EOF".to_string())),
-                            Some(x) => {
-                                return Err(
-                                    self.error(format!("expected +/- but got '{}'", x as char))
-                                )
-                            }
-                        };
-                        let hr_off = self.read_number(10)? as i16;
-                        let min_off = self.read_number(10)? as i16;
-
-                        let total_min_off = sign * ((hr_off * 60) + min_off);
-                        let tz = TZ::Known(time::UtcOffset::minutes(total_min_off));
-                        let res = Time::new(year, month, day, hour, minute, tz)?;
-
-                        match self.read()? {
-                            // EOF or EOL.
-                            None | Some(b'\n') => Ok(Some((res, true))),
-                            // End of current entry.
-                            Some(b',') => Ok(Some((res, false))),
-                            // Anything else.
-                            Some(x) => {
-                                Err(self.error(format!("expected '\\n' but got '{}'", x as char)))
-                            }
-                        }
-                    }
+                    Some(b',') => Ok((res, false)),
                     // Anything else.
-                    Some(x) => Err(self.error(format!(
-                        "expected newline, comma, or space, but got '{}'",
-                        x as char
-                    ))),
+                    x => Err(self.unexpected("newline or comma", x)),
                 }
             }
+            // Anything else.
+            x => Err(self.unexpected("newline, comma, or space", x)),
         }
     }
 
+    /// Like Time::new, but an out of range field is reported as a ParseError. `at` is where the
+    /// time started.
+    #[allow(clippy::too_many_arguments)]
+    fn new_time(
+        &self,
+        at: (usize, usize),
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        tz: TZ,
+    ) -> Result<Time, ParseError> {
+        Time::new(year, month, day, hour, minute, tz).map_err(|e| ParseError::InvalidTime {
+            line: at.0,
+            col: at.1,
+            text: format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}",
+                year, month, day, hour, minute
+            ),
+            reason: e.to_string(),
+        })
+    }
+
     fn implied_tz(&self) -> TZ {
-        TZ::Implied(self.default_tz)
+        TZ::Implied(self.default_tz.clone())
     }
 
-    fn read_year(&mut self) -> Result<Option<u16>, Box<dyn Error>> {
-        loop {
-            match self.read()? {
-                None => return Ok(None),
-                Some(b' ') | Some(b'\n') => (),
-                Some(digit) => {
-                    let digit = self.parse_digit(digit)?;
-                    let year = 1000 * digit + self.read_number(100)?;
-                    return Ok(Some(year));
-                }
+    fn parse_year(&mut self, first_digit: u8) -> Result<u16, ParseError> {
+        let digit = self.parse_digit(first_digit)?;
+        Ok(1000 * digit + self.read_number(100)?)
+    }
+
+    /// Read the rest of the line. It has to be UTF-8, but the whole line is read either way, so
+    /// that parsing can go on from the next one.
+    fn read_line(&mut self) -> Result<String, ParseError> {
+        let (line, col) = (self.line, self.col);
+        let bytes = self.read_raw_line()?;
+        String::from_utf8(bytes).map_err(|e| {
+            let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+            ParseError::InvalidUtf8 {
+                line,
+                col: col + String::from_utf8_lossy(valid).chars().count() + 1,
             }
+        })
+    }
+
+    /// Read the rest of the line as bytes, without the newline.
+    fn read_raw_line(&mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.reader.read_until(b'\n', &mut bytes)?;
+        self.offset += bytes.len() as u64;
+        let newline = bytes.last() == Some(&b'\n');
+        if newline {
+            bytes.pop();
+        }
+        self.last = (
+            self.line,
+            self.col + String::from_utf8_lossy(&bytes).chars().count(),
+        );
+        if newline {
+            self.line += 1;
+            self.col = 0;
         }
+        Ok(bytes)
     }
 
-    fn read_line(&mut self) -> Result<String, Box<dyn Error>> {
-        let mut s = String::new();
-        self.reader.read_line(&mut s)?;
-        if let Some('\n') = s.chars().last() {
-            s.pop();
+    /// The rest of the line after the stop time is an optional label, optionally followed by a
+    /// comma and the resume marker. Labels can have commas in them, so only a last field that is
+    /// the marker counts as one.
+    fn parse_label_and_marker(&mut self) -> Result<(Option<String>, bool), ParseError> {
+        let line = self.read_line()?;
+        let (label, resumed) = match line.rsplit_once(',') {
+            Some((label, marker)) if marker.trim() == RESUME_MARKER => (label, true),
+            _ => (line.as_str(), false),
+        };
+        let label = Some(label.trim())
+            .filter(|label| !label.is_empty())
+            .map(str::to_string);
+        Ok((label, resumed))
+    }
+
+    /// After a syntax error, move on to the start of the next line. If the error was at the end
+    /// of a line, the newline has already been read.
+    fn skip_rest_of_line(&mut self) -> Result<(), ParseError> {
+        if self.col > 0 {
+            self.read_raw_line()?;
         }
-        Ok(s)
+        Ok(())
     }
 
-    fn read_expected(&mut self, expected: u8) -> Result<(), Box<dyn Error>> {
+    fn read_expected(&mut self, expected: u8) -> Result<(), ParseError> {
         match self.read()? {
-            None => Err(self.error(format!("expected '{}' but got EOF", expected as char))),
-            Some(x) => {
-                if x == expected {
-                    Ok(())
-                } else {
-                    Err(self.error(format!(
-                        "expected '{}' but got '{}'",
-                        expected as char, x as char
-                    )))
-                }
-            }
+            Some(x) if x == expected => Ok(()),
+            x => Err(self.unexpected(&format!("{:?}", expected as char), x)),
         }
     }
 
-    fn read_number(&mut self, scale: u16) -> Result<u16, Box<dyn Error>> {
+    fn read_number(&mut self, scale: u16) -> Result<u16, ParseError> {
         match self.read()? {
-            None => Err(self.error("expected a digit but got EOF".to_string())),
+            None => Err(self.unexpected("a digit", None)),
             Some(digit) => {
                 let digit = self.parse_digit(digit)?;
                 match scale {
@@ -234,6 +490,10 @@ impl<R: BufRead> Parser<R> {
         }
     }
 
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
     fn read(&mut self) -> io::Result<Option<u8>> {
         let mut buf = [0; 1];
         let len = self.reader.read(&mut buf)?;
@@ -241,6 +501,8 @@ impl<R: BufRead> Parser<R> {
             return Ok(None);
         }
         let c = buf[0];
+        self.offset += 1;
+        self.last = (self.line, self.col + 1);
         if c == b'\n' {
             self.line += 1;
             self.col = 0;
@@ -250,25 +512,71 @@ impl<R: BufRead> Parser<R> {
         Ok(Some(c))
     }
 
-    fn parse_digit(&self, digit: u8) -> Result<u16, Box<dyn Error>> {
+    fn parse_digit(&mut self, digit: u8) -> Result<u16, ParseError> {
         match digit {
             b'0' | b'1' | b'2' | b'3' | b'4' | b'5' | b'6' | b'7' | b'8' | b'9' => {
                 Ok((digit - b'0') as u16)
             }
-            x => Err(self.error(format!("expected a digit but got '{}'", x as char))),
+            x => Err(self.unexpected("a digit", Some(x))),
         }
     }
 
-    fn error(&self, message: String) -> Box<dyn Error> {
-        let message = format!("line {}, col {}: {}", self.line, self.col, message);
-        Box::new(ParseError { message })
+    /// An error about the last byte that was read, or about EOF if there wasn't one.
+    fn unexpected(&mut self, expected: &str, found: Option<u8>) -> ParseError {
+        let (line, col) = self.last;
+        let expected = expected.to_string();
+        match found {
+            None => ParseError::UnexpectedEof {
+                line,
+                col,
+                expected,
+            },
+            Some(x) => ParseError::UnexpectedChar {
+                line,
+                col,
+                expected,
+                found: self.finish_char(x),
+            },
+        }
+    }
+
+    /// The character that starts with the last byte that was read. The rest of its bytes are
+    /// read too, since the line gets skipped after an unexpected character anyway.
+    fn finish_char(&mut self, first: u8) -> char {
+        let len = match first {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        let mut bytes = vec![first];
+        while bytes.len() < len {
+            match self.peek() {
+                Ok(Some(b)) if b & 0xc0 == 0x80 => {
+                    self.reader.consume(1);
+                    self.offset += 1;
+                    bytes.push(b);
+                }
+                _ => break,
+            }
+        }
+        std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_entries, write_entries, Time, TimeEntry, TZ};
+    use super::{
+        entries, parse_entries, parse_entries_recovering, parse_time_entries, write_entries,
+        ParseError, Parsed, Time, TimeEntry, TZ,
+    };
+    use crate::timesource::mock_time::mock_time_in;
+    use crate::zone::Zone;
     use crate::{entry::Entry, timesource::real_time::DefaultTimeSource};
+    use time::{date, offset, time};
 
     type TestRes = Result<(), Box<dyn std::error::Error>>;
 
@@ -298,12 +606,36 @@ mod tests {
             vec![Entry::Time(TimeEntry {
                 start: mktime(2020, 1, 2, 12, 34, None)?,
                 stop: None,
+                label: None,
+                notes: vec![],
+                resumed: false,
             })],
             actual
         );
         Ok(())
     }
 
+    #[test]
+    fn test_no_tz_in_zone() -> TestRes {
+        let zone = Zone::named("EST5EDT,M3.2.0,M11.1.0")?;
+        let ts = mock_time_in(date!(2023 - 07 - 03), time!(12:00), zone);
+        let input = "2023-01-02 09:00,2023-01-02 17:00\n\
+                     2023-07-03 09:00\n";
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let offsets: Vec<_> = entries
+            .iter()
+            .map(|te| te.start.datetime().offset())
+            .collect();
+        assert_eq!(vec![offset!(-05:00), offset!(-04:00)], offsets);
+        let mut output = vec![];
+        write_entries(
+            &mut output,
+            &entries.into_iter().map(Entry::from).collect::<Vec<_>>(),
+        )?;
+        assert_eq!(input, String::from_utf8(output)?);
+        Ok(())
+    }
+
     #[test]
     fn test_start_with_neg_tz() -> TestRes {
         let actual = parse_entries("2020-01-02 12:34 -1001\n".as_bytes(), &DefaultTimeSource)?;
@@ -311,6 +643,9 @@ mod tests {
             vec![Entry::Time(TimeEntry {
                 start: mktime(2020, 1, 2, 12, 34, Some(-601))?,
                 stop: None,
+                label: None,
+                notes: vec![],
+                resumed: false,
             })],
             actual
         );
@@ -324,6 +659,9 @@ mod tests {
             vec![Entry::Time(TimeEntry {
                 start: mktime(2020, 1, 2, 12, 34, Some(601))?,
                 stop: None,
+                label: None,
+                notes: vec![],
+                resumed: false,
             })],
             actual
         );
@@ -340,6 +678,9 @@ mod tests {
             vec![Entry::Time(TimeEntry {
                 start: mktime(2020, 1, 2, 12, 34, Some(-240))?,
                 stop: Some(mktime(2020, 1, 2, 13, 34, Some(-240))?),
+                label: None,
+                notes: vec![],
+                resumed: false,
             })],
             actual
         );
@@ -356,6 +697,9 @@ mod tests {
             vec![Entry::Time(TimeEntry {
                 start: mktime(2020, 1, 2, 12, 34, Some(600))?,
                 stop: Some(mktime(2020, 1, 2, 13, 34, Some(-240))?),
+                label: None,
+                notes: vec![],
+                resumed: false,
             })],
             actual
         );
@@ -388,6 +732,9 @@ mod tests {
             &TimeEntry {
                 start: mktime(2020, 2, 2, 11, 11, None)?,
                 stop: None,
+                label: None,
+                notes: vec![],
+                resumed: false,
             },
             actual[2].time()
         );
@@ -413,11 +760,17 @@ mod tests {
                 Entry::Time(TimeEntry {
                     start: mktime(2020, 1, 2, 12, 34, Some(-240))?,
                     stop: Some(mktime(2020, 1, 2, 13, 34, Some(-240))?),
+                    label: None,
+                    notes: vec![],
+                    resumed: false,
                 }),
                 Entry::note(" and another one "),
                 Entry::Time(TimeEntry {
                     start: mktime(2020, 1, 3, 12, 34, Some(-240))?,
                     stop: Some(mktime(2020, 1, 3, 14, 0, Some(-240))?),
+                    label: None,
+                    notes: vec![],
+                    resumed: false,
                 }),
                 Entry::note(" lastly blah blah; no newline, oh the nerve!"),
             ],
@@ -426,5 +779,364 @@ mod tests {
         Ok(())
     }
 
-    // TODO - tests for errors?
+    #[test]
+    fn test_annotations_round_trip() -> TestRes {
+        let original = "# first\n\
+                        2020-01-02 12:34 -0400,2020-01-02 13:34 -0400\n\
+                        #and another one \n";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        let mut output = Vec::new();
+        write_entries(&mut output, &actual)?;
+        assert_eq!(std::str::from_utf8(&output)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_labels() -> TestRes {
+        let original = "2020-01-02 12:34 -0400,2020-01-02 13:34 -0400,acme\n\
+                        2020-01-03 09:00,2020-01-03 10:30, initech \n\
+                        2020-02-02 11:11,,acme\n";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        assert_eq!(
+            vec![
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 2, 12, 34, Some(-240))?,
+                    stop: Some(mktime(2020, 1, 2, 13, 34, Some(-240))?),
+                    label: Some("acme".to_string()),
+                    notes: vec![],
+                    resumed: false,
+                }),
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 3, 9, 0, None)?,
+                    stop: Some(mktime(2020, 1, 3, 10, 30, None)?),
+                    label: Some("initech".to_string()),
+                    notes: vec![],
+                    resumed: false,
+                }),
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 2, 2, 11, 11, None)?,
+                    stop: None,
+                    label: Some("acme".to_string()),
+                    notes: vec![],
+                    resumed: false,
+                }),
+            ],
+            actual
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_labels_round_trip() -> TestRes {
+        let original = "2020-01-02 12:34 +1000,2020-01-02 13:34 -0400\n\
+                        2020-01-03 09:00,2020-01-03 10:30,acme\n\
+                        2020-02-02 11:11,,initech\n";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        let mut output = Vec::new();
+        write_entries(&mut output, &actual)?;
+        assert_eq!(std::str::from_utf8(&output)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_marker() -> TestRes {
+        let original = "2020-01-02 12:34,2020-01-02 13:34,acme\n\
+                        2020-01-02 14:00,2020-01-02 15:00,acme,resume\n\
+                        2020-01-02 15:30,2020-01-02 16:00,,resume\n\
+                        2020-01-02 16:10,, acme , resume \n";
+        let actual = parse_time_entries(original.as_bytes(), &DefaultTimeSource)?;
+        assert_eq!(
+            vec![false, true, true, true],
+            actual.iter().map(|e| e.resumed).collect::<Vec<_>>()
+        );
+        assert_eq!(Some("acme".to_string()), actual[3].label);
+        assert_eq!(None, actual[2].label);
+        assert!(!actual[3].is_finished());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_marker_round_trip() -> TestRes {
+        let original = "2020-01-02 12:34,2020-01-02 13:34\n\
+                        2020-01-02 14:00,2020-01-02 15:00,acme,resume\n\
+                        2020-01-02 15:30,2020-01-02 16:00,,resume\n\
+                        2020-01-02 16:10,,,resume\n\
+                        \x20 # with a note\n";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        let mut output = Vec::new();
+        write_entries(&mut output, &actual)?;
+        assert_eq!(std::str::from_utf8(&output)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_labels_with_commas() -> TestRes {
+        let original = "2020-01-02 12:34,2020-01-02 13:34,acme, site\n\
+                        2020-01-02 14:00,2020-01-02 15:00,acme, site,resume\n\
+                        2020-01-02 15:30,,a,b,c\n";
+        let actual = parse_time_entries(original.as_bytes(), &DefaultTimeSource)?;
+        assert_eq!(
+            vec![Some("acme, site"), Some("acme, site"), Some("a,b,c")],
+            actual
+                .iter()
+                .map(|e| e.label.as_deref())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![false, true, false],
+            actual.iter().map(|e| e.resumed).collect::<Vec<_>>()
+        );
+        let mut output = Vec::new();
+        write_entries(
+            &mut output,
+            &parse_entries(original.as_bytes(), &DefaultTimeSource)?,
+        )?;
+        assert_eq!(std::str::from_utf8(&output)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_with_comma_then_more_entries() -> TestRes {
+        let original = "2020-01-02 12:34,\n\
+                        2020-01-03 09:00,2020-01-03 10:30\n";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        assert_eq!(2, actual.len());
+        assert!(!actual[0].time().is_finished());
+        assert!(actual[1].time().is_finished());
+        Ok(())
+    }
+
+    #[test]
+    fn test_attached_notes() -> TestRes {
+        let original = "# free\n\
+                        2020-01-02 12:34 -0400,2020-01-02 13:34 -0400,acme\n\
+                        \x20 # first\n\
+                        \x20   # second  \n\
+                        # not attached\n\
+                        2020-01-03 12:34 -0400\n\
+                        \x20 # still working";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        assert_eq!(
+            vec![
+                Entry::note(" free"),
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 2, 12, 34, Some(-240))?,
+                    stop: Some(mktime(2020, 1, 2, 13, 34, Some(-240))?),
+                    label: Some("acme".to_string()),
+                    notes: vec!["first".to_string(), "second".to_string()],
+                    resumed: false,
+                }),
+                Entry::note(" not attached"),
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 3, 12, 34, Some(-240))?,
+                    stop: None,
+                    label: None,
+                    notes: vec!["still working".to_string()],
+                    resumed: false,
+                }),
+            ],
+            actual
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_attached_notes_round_trip() -> TestRes {
+        let original = "2020-01-02 12:34 -0400,2020-01-02 13:34 -0400\n\
+                        \x20 # first\n\
+                        \x20 # second\n\
+                        2020-01-03 12:34 -0400,,acme\n\
+                        \x20 # third\n";
+        let actual = parse_entries(original.as_bytes(), &DefaultTimeSource)?;
+        let mut output = Vec::new();
+        write_entries(&mut output, &actual)?;
+        assert_eq!(std::str::from_utf8(&output)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_position() {
+        let original = "2020-01-02 12:34,2020-01-02 13:34\n\
+                        2020-01-0x 12:34\n";
+        let err = parse_entries(original.as_bytes(), &DefaultTimeSource).unwrap_err();
+        assert_eq!(
+            "line 2, col 10: expected a digit but got 'x'",
+            err.to_string()
+        );
+
+        // The error is on the line that ended too soon, not the line after it.
+        let original = "2020-01-02 12:34,2020-01-02\n\
+                        2020-01-02 14:00\n";
+        let err = parse_entries(original.as_bytes(), &DefaultTimeSource).unwrap_err();
+        assert!(err.to_string().starts_with("line 1, col 28: "), "{}", err);
+
+        let original = "2020-13-02 12:34\n";
+        let err = parse_entries(original.as_bytes(), &DefaultTimeSource).unwrap_err();
+        assert!(err.to_string().starts_with("line 1, col 1: "), "{}", err);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let parse = |s: &str| parse_entries(s.as_bytes(), &DefaultTimeSource).unwrap_err();
+
+        match parse("2020-01-02 12:34,2020-01-0") {
+            ParseError::UnexpectedEof {
+                line: 1,
+                col: 26,
+                expected,
+            } => assert_eq!("a digit", expected),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        match parse("2020-01-02 12:34\n2020/01-02 12:34\n") {
+            ParseError::UnexpectedChar {
+                line: 2,
+                col: 5,
+                expected,
+                found: '/',
+            } => assert_eq!("'-'", expected),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        match parse("2020-01-02 09:00, 2020-01-02 24:30\n") {
+            ParseError::InvalidTime {
+                line: 1,
+                col: 19,
+                text,
+                ..
+            } => assert_eq!("2020-01-02 24:30", text),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_entry_start() -> TestRes {
+        let original = "  2020-01-02 12:34\n  # attached\n\n# free\n2020-01-03 12:34\n";
+        let mut iter = entries(original.as_bytes(), &DefaultTimeSource);
+        let mut starts = vec![];
+        while let Some(entry) = iter.next() {
+            entry?;
+            starts.push(iter.entry_start());
+        }
+        assert_eq!(vec![2, 33, 40], starts);
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_iterator() -> TestRes {
+        let original = "# a note\n\
+                        2020-01-02 12:34,2020-01-02 13:34\n\
+                        2020-01-0x 12:34,2020-01-02 13:34\n\
+                        2020-01-03 12:34,2020-01-03 13:34\n";
+        let mut iter = entries(original.as_bytes(), &DefaultTimeSource);
+        assert_eq!(Entry::note(" a note"), iter.next().unwrap()?);
+        assert_eq!(
+            mktime(2020, 1, 2, 12, 34, None)?,
+            iter.next().unwrap()?.time().start
+        );
+        assert_eq!(Some((3, 10)), iter.next().unwrap().unwrap_err().position());
+        assert_eq!(
+            mktime(2020, 1, 3, 12, 34, None)?,
+            iter.next().unwrap()?.time().start
+        );
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+
+        let starts = entries(original.as_bytes(), &DefaultTimeSource)
+            .time_entries()
+            .filter_map(Result::ok)
+            .map(|entry| entry.start)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                mktime(2020, 1, 2, 12, 34, None)?,
+                mktime(2020, 1, 3, 12, 34, None)?
+            ],
+            starts
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovering() -> TestRes {
+        let original = "2020-01-02 12:34,2020-01-02 13:34\n\
+                        2020-01-0x 12:34,2020-01-02 13:34\n\
+                        2020-01-03 12:34,2020-01-03\n\
+                        2020-01-04 12:34,2020-01-04 13:34,acme,paused\n\
+                        # a note\n\
+                        2020-01-05 12:34,2020-01-05 13:34\n\
+                        2020-01-06 1";
+        let Parsed { entries, errors } =
+            parse_entries_recovering(original.as_bytes(), &DefaultTimeSource)?;
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "line 2, col 10: expected a digit but got 'x'".to_string(),
+                "line 3, col 28: expected ' ' but got '\\n'".to_string(),
+                "line 7, col 12: expected a digit but got EOF".to_string(),
+            ],
+            errors
+        );
+        assert_eq!(
+            vec![
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 2, 12, 34, None)?,
+                    stop: Some(mktime(2020, 1, 2, 13, 34, None)?),
+                    label: None,
+                    notes: vec![],
+                    resumed: false,
+                }),
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 4, 12, 34, None)?,
+                    stop: Some(mktime(2020, 1, 4, 13, 34, None)?),
+                    label: Some("acme,paused".to_string()),
+                    notes: vec![],
+                    resumed: false,
+                }),
+                Entry::note(" a note"),
+                Entry::Time(TimeEntry {
+                    start: mktime(2020, 1, 5, 12, 34, None)?,
+                    stop: Some(mktime(2020, 1, 5, 13, 34, None)?),
+                    label: None,
+                    notes: vec![],
+                    resumed: false,
+                }),
+            ],
+            entries
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovering_from_non_ascii() -> TestRes {
+        let mut original = b"2020-01-04 11:00 \xe2\x80\x93 work\n\
+                             2020-01-05 \xc3\xa9\n\
+                             # caf\xe9\n\
+                             2020-01-06 12:34,2020-01-06 13:34,caf\xc3\xa9\n"
+            .to_vec();
+        original.extend_from_slice(b"2020-01-07 12:34,2020-01-07 13:34,\xff\n");
+        let Parsed { entries, errors } =
+            parse_entries_recovering(&original[..], &DefaultTimeSource)?;
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "line 1, col 18: expected +/- but got '–'".to_string(),
+                "line 2, col 12: expected a digit but got 'é'".to_string(),
+                "line 3, col 6: invalid UTF-8".to_string(),
+                "line 5, col 35: invalid UTF-8".to_string(),
+            ],
+            errors
+        );
+        assert_eq!(
+            vec![Entry::Time(TimeEntry {
+                start: mktime(2020, 1, 6, 12, 34, None)?,
+                stop: Some(mktime(2020, 1, 6, 13, 34, None)?),
+                label: Some("café".to_string()),
+                notes: vec![],
+                resumed: false,
+            })],
+            entries
+        );
+        Ok(())
+    }
 }
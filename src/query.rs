@@ -1,11 +1,13 @@
 use std::error::Error;
 use std::path::PathBuf;
 
-use time::{Duration, OffsetDateTime};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
 
+use crate::calendar::Calendar;
 use crate::entry::{into_time_entries, Entry, TimeEntry};
 use crate::extents;
-use crate::file::{read_entries, read_last_entries, t_open};
+use crate::file::{read_entries, read_entries_since, read_last_entries, t_open};
+use crate::timesource::real_time::DefaultTimeSource;
 use crate::timesource::TimeSource;
 
 pub fn for_cli<TS>(ts: TS) -> Context<TS> {
@@ -22,29 +24,65 @@ pub struct Context<TS> {
 }
 
 impl<TS: TimeSource> Context<TS> {
+    /// The last 100 entries.
     pub fn tail(&self) -> Result<EntriesResult, Box<dyn Error>> {
         let entries = self.read_last_entries(100)?;
         let entries = into_time_entries(entries);
         Ok(EntriesResult { entries })
     }
 
+    /// Every entry, including the ones in archives.
+    #[deprecated(note = "parses the whole file every time; use `recent` or `this_week` instead")]
     pub fn all(&self) -> Result<EntriesResult, Box<dyn Error>> {
         let entries = self.read_entries()?;
         let entries = into_time_entries(entries);
         Ok(EntriesResult { entries })
     }
 
+    /// The entries that overlap this week, including an entry that's still running.
+    pub fn this_week(&self, cal: &Calendar) -> Result<EntriesResult, Box<dyn Error>> {
+        let (start_week, _) = extents::this_week(cal);
+        let entries = self.read_entries_since(start_week)?;
+        let entries = into_time_entries(entries);
+        Ok(EntriesResult { entries })
+    }
+
+    /// The entries since the start of the week `previous_weeks` before this one. That's all
+    /// that `recent_weeks` needs to compare this week with those weeks, so the rest of the file
+    /// isn't parsed.
+    pub fn recent(
+        &self,
+        previous_weeks: i16,
+        cal: &Calendar,
+    ) -> Result<EntriesResult, Box<dyn Error>> {
+        let (start_week, _) = extents::this_week(cal);
+        let since = start_week - Duration::weeks(previous_weeks as i64);
+        let entries = self.read_entries_since(since)?;
+        let entries = into_time_entries(entries);
+        Ok(EntriesResult { entries })
+    }
+
+    fn read_entries(&self) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+        match &self.tf {
+            None => read_entries(&self.ts),
+            Some(tf) => Ok(t_open(tf)?.read_entries(&self.ts)?),
+        }
+    }
+
     fn read_last_entries(&self, n: u64) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
         match &self.tf {
             None => read_last_entries(n, &self.ts),
-            Some(tf) => t_open(tf)?.read_last_entries(n, &self.ts),
+            Some(tf) => Ok(t_open(tf)?.read_last_entries(n, &self.ts)?),
         }
     }
 
-    fn read_entries(&self) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    fn read_entries_since(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
         match &self.tf {
-            None => read_entries(&self.ts),
-            Some(tf) => t_open(tf)?.read_entries(&self.ts),
+            None => read_entries_since(since, &self.ts),
+            Some(tf) => Ok(t_open(tf)?.read_entries_since(since, &self.ts)?),
         }
     }
 }
@@ -79,15 +117,20 @@ impl EntriesResult {
         entries_between(&self.entries, range.0, range.1)
     }
 
-    pub fn recent_weeks(&self, previous_weeks: i16) -> Vec<PreviousWeek<'_>> {
-        let (start_week, now) = extents::this_week();
+    pub fn recent_weeks(&self, previous_weeks: i16, cal: &Calendar) -> Vec<PreviousWeek<'_>> {
+        let (start_week, now) = extents::this_week(cal);
+        // Go back by whole weeks on the clock, which isn't always a whole number of hours when
+        // the clocks changed in between.
+        let zone = DefaultTimeSource.zone();
+        let local = |dt: OffsetDateTime| PrimitiveDateTime::new(dt.date(), dt.time());
         (0..previous_weeks)
             .rev()
             .map(|off| {
                 let off = Duration::weeks((1 + off) as i64);
                 PreviousWeek {
-                    start: start_week - off,
-                    todayish: now - off,
+                    start: zone.assume(local(start_week) - off),
+                    stop: zone.assume(local(start_week) - off + Duration::week()),
+                    todayish: zone.assume(local(now) - off),
                     entries: &self.entries,
                 }
             })
@@ -97,6 +140,7 @@ impl EntriesResult {
 
 pub struct PreviousWeek<'a> {
     pub start: time::OffsetDateTime,
+    stop: time::OffsetDateTime,
     todayish: time::OffsetDateTime,
     entries: &'a [TimeEntry],
 }
@@ -107,7 +151,7 @@ impl<'a> PreviousWeek<'a> {
     }
 
     pub fn total_minutes(&self) -> i64 {
-        minutes_between(self.entries, self.start, self.start + Duration::days(7))
+        minutes_between(self.entries, self.start, self.stop)
     }
 }
 
@@ -0,0 +1,200 @@
+use crate::entry::{Entry, TimeEntry};
+use std::fmt::{self, Display, Formatter};
+
+/// Problem is something that `t validate` complains about and `t repair` knows how to fix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Problem {
+    /// The entry stops before it starts. The fix is to swap the times.
+    Reversed,
+    /// The entry was never stopped, but another one starts after it. The fix is to stop it when
+    /// the next one starts.
+    Unfinished,
+    /// The entry stops after the next one starts. The fix is to stop it when the next one starts.
+    Overlap,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reversed => write!(f, "stops before it starts"),
+            Self::Unfinished => write!(f, "was never stopped, but another entry starts after it"),
+            Self::Overlap => write!(f, "stops after the next entry starts"),
+        }
+    }
+}
+
+/// Fix is a change to one time entry.
+#[derive(Debug, PartialEq)]
+pub struct Fix {
+    pub problem: Problem,
+    pub before: TimeEntry,
+    pub after: TimeEntry,
+}
+
+/// Repairs is what came of repairing a list of entries.
+#[derive(Debug, Default, PartialEq)]
+pub struct Repairs {
+    pub applied: Vec<Fix>,
+    pub declined: Vec<Fix>,
+    /// Problems that have to be fixed by hand.
+    pub unfixable: Vec<String>,
+}
+
+/// Go through the entries in order and offer each fix to `accept`. Accepted fixes are made in
+/// place straight away, so the fixes after them take them into account.
+pub fn repair(entries: &mut [Entry], mut accept: impl FnMut(&Fix) -> bool) -> Repairs {
+    let mut repairs = Repairs::default();
+    let mut prev = None;
+    for i in 0..entries.len() {
+        let (earlier, rest) = entries.split_at_mut(i);
+        let te = match &mut rest[0] {
+            Entry::Time(te) => te,
+            Entry::Note(_) => continue,
+        };
+        if let Some(stop) = te.stop.clone() {
+            if stop.datetime() < te.start.datetime() {
+                let after = TimeEntry {
+                    start: stop,
+                    stop: Some(te.start.clone()),
+                    ..te.clone()
+                };
+                offer(&mut repairs, te, Problem::Reversed, after, &mut accept);
+            }
+        }
+        if let Some(Entry::Time(prev_te)) = prev.and_then(|p| earlier.get_mut(p)) {
+            let problem = match &prev_te.stop {
+                None => Some(Problem::Unfinished),
+                Some(stop) if stop.datetime() > te.start.datetime() => Some(Problem::Overlap),
+                Some(_) => None,
+            };
+            match problem {
+                None => (),
+                // Stopping the previous entry here would make it stop before it starts.
+                Some(_) if te.start.datetime() < prev_te.start.datetime() => {
+                    repairs.unfixable.push(format!(
+                        "{} starts before the entry before it, {}",
+                        te.start, prev_te.start
+                    ))
+                }
+                Some(problem) => {
+                    let after = TimeEntry {
+                        stop: Some(te.start.clone()),
+                        ..prev_te.clone()
+                    };
+                    offer(&mut repairs, prev_te, problem, after, &mut accept);
+                }
+            }
+        }
+        prev = Some(i);
+    }
+    repairs
+}
+
+fn offer(
+    repairs: &mut Repairs,
+    te: &mut TimeEntry,
+    problem: Problem,
+    after: TimeEntry,
+    accept: &mut impl FnMut(&Fix) -> bool,
+) {
+    let fix = Fix {
+        problem,
+        before: te.clone(),
+        after,
+    };
+    if accept(&fix) {
+        *te = fix.after.clone();
+        repairs.applied.push(fix);
+    } else {
+        repairs.declined.push(fix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{repair, Problem};
+    use crate::entry::Entry;
+    use crate::parser::{parse_entries, write_entries};
+    use crate::timesource::mock_time::mock_time;
+    use pretty_assertions::assert_eq;
+    use time::{date, offset, time};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    fn parse(s: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+        let ts = mock_time(date!(2020 - 07 - 16), time!(10:00), offset!(-04:00));
+        Ok(parse_entries(s.as_bytes(), &ts)?)
+    }
+
+    fn write(entries: &[Entry]) -> Result<String, Box<dyn std::error::Error>> {
+        let mut output = vec![];
+        write_entries(&mut output, entries)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn test_repair_everything() -> TestRes {
+        let mut entries = parse(
+            "2020-07-15 09:00 -0400\n\
+             # a free note\n\
+             2020-07-15 10:00 -0400,2020-07-15 11:30 -0400,code\n\
+             2020-07-15 13:00 -0400,2020-07-15 11:00 -0400\n\
+             2020-07-15 14:00 -0400\n",
+        )?;
+        let mut offered = vec![];
+        let repairs = repair(&mut entries, |fix| {
+            offered.push(fix.problem);
+            true
+        });
+        assert_eq!(
+            vec![Problem::Unfinished, Problem::Reversed, Problem::Overlap],
+            offered
+        );
+        assert_eq!(3, repairs.applied.len());
+        assert!(repairs.unfixable.is_empty());
+        assert_eq!(
+            "2020-07-15 09:00 -0400,2020-07-15 10:00 -0400\n\
+             # a free note\n\
+             2020-07-15 10:00 -0400,2020-07-15 11:00 -0400,code\n\
+             2020-07-15 11:00 -0400,2020-07-15 13:00 -0400\n\
+             2020-07-15 14:00 -0400\n",
+            write(&entries)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_declined() -> TestRes {
+        let input = "2020-07-15 13:00 -0400,2020-07-15 11:00 -0400\n\
+                     2020-07-15 14:00 -0400,2020-07-15 16:00 -0400\n\
+                     2020-07-15 15:00 -0400\n";
+        let mut entries = parse(input)?;
+        let repairs = repair(&mut entries, |fix| fix.problem == Problem::Overlap);
+        assert_eq!(1, repairs.declined.len());
+        assert_eq!(1, repairs.applied.len());
+        assert_eq!(
+            "2020-07-15 13:00 -0400,2020-07-15 11:00 -0400\n\
+             2020-07-15 14:00 -0400,2020-07-15 15:00 -0400\n\
+             2020-07-15 15:00 -0400\n",
+            write(&entries)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_out_of_order() -> TestRes {
+        let input = "2020-07-15 13:00 -0400,2020-07-15 14:00 -0400\n\
+                     2020-07-15 12:00 -0400,2020-07-15 12:30 -0400\n";
+        let mut entries = parse(input)?;
+        let repairs = repair(&mut entries, |_| true);
+        assert!(repairs.applied.is_empty());
+        assert_eq!(input, write(&entries)?);
+        assert_eq!(
+            vec![
+                "2020-07-15 12:00 -0400 starts before the entry before it, 2020-07-15 13:00 -0400"
+            ],
+            repairs.unfixable
+        );
+        Ok(())
+    }
+}
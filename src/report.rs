@@ -1,3 +1,4 @@
 pub mod all;
+pub mod breaks;
 pub mod days;
 pub mod pto;
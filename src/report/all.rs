@@ -1,14 +1,16 @@
+use crate::calendar::Calendar;
 use crate::entry::TimeEntry;
-use crate::iter::{each_day_in_week, each_week};
 use crate::timesource::TimeSource;
+use crate::totals::{each_week_totals, WeekTotals};
 use std::fmt::Debug;
-use time::{Date, Duration, OffsetDateTime};
+use time::Date;
 
 #[derive(Debug, PartialEq)]
 pub struct All<T: PartialEq> {
     pub start: Date,
     pub minutes: i64,
     pub segments: usize,
+    pub pauses: usize,
     pub analysis: Option<AllAnalysis<T>>,
 }
 
@@ -24,48 +26,33 @@ pub struct AllAnalysis<T: PartialEq> {
 pub fn calc<T: PartialEq + Copy, TS: TimeSource>(
     entries: Vec<TimeEntry>,
     sparks: &[T],
+    cal: &Calendar,
     ts: &TS,
 ) -> Vec<All<T>> {
-    each_week(entries, ts)
-        .map(|(start, entries)| calc_all_week(start, entries, sparks, ts))
-        .collect()
+    calc_from_totals(each_week_totals(entries, cal, ts), sparks)
 }
 
-const ONE_DAY: Duration = Duration::days(1);
-const SUNDAY_TO_SATURDAY: Duration = Duration::days(6);
-
-fn calc_all_week<T: PartialEq + Copy, TS: TimeSource>(
-    start: Date,
-    entries: Vec<TimeEntry>,
+/// Like calc, for weeks that have already been added up, e.g. by the cache.
+pub fn calc_from_totals<T: PartialEq + Copy>(
+    weeks: impl IntoIterator<Item = WeekTotals>,
     sparks: &[T],
-    ts: &TS,
-) -> All<T> {
-    let (segments, minutes, analysis) = if entries.len() < 2 {
-        let stop = start + SUNDAY_TO_SATURDAY;
-        (
-            entries.len(),
-            minutes_between_days(&entries, start, stop, ts),
-            None,
-        )
+) -> Vec<All<T>> {
+    weeks
+        .into_iter()
+        .map(|week| calc_all_week(week, sparks))
+        .collect()
+}
+
+fn calc_all_week<T: PartialEq + Copy>(week: WeekTotals, sparks: &[T]) -> All<T> {
+    let (segments, minutes, analysis) = if week.entries < 2 {
+        (week.entries, week.minutes, None)
     } else {
-        let entry_minutes_by_day: Vec<Vec<i64>> = each_day_in_week(entries, start, ts)
-            .filter(|(_, entries)| !entries.is_empty())
-            .map(|(start, entries)| {
-                let start = start.midnight().assume_offset(ts.local_offset());
-                let stop = start + ONE_DAY;
-                entries
-                    .into_iter()
-                    .map(|entry| entry.minutes_between(start, stop))
-                    .collect()
-            })
-            .collect();
-        let entry_minutes: Vec<i64> = entry_minutes_by_day
+        let entry_minutes: Vec<i64> = week
+            .segments
             .iter()
             .flat_map(|entries| entries.iter().copied())
             .collect();
-        let segments = entry_minutes_by_day
-            .iter()
-            .fold(0, |sum, minutes| sum + minutes.len());
+        let segments = entry_minutes.len();
         let total_minutes = entry_minutes.iter().sum();
         let mean = total_minutes / segments as i64;
         let mut min = 10080;
@@ -81,7 +68,12 @@ fn calc_all_week<T: PartialEq + Copy, TS: TimeSource>(
             let diff = m - mean;
             sumsq += diff * diff;
         }
-        let stddev = sqrtint(sumsq / (segments as i64 - 1));
+        // Two entries can end up as one segment if one resumed the other.
+        let stddev = if segments > 1 {
+            sqrtint(sumsq / (segments as i64 - 1))
+        } else {
+            0
+        };
         (
             segments,
             total_minutes,
@@ -90,7 +82,8 @@ fn calc_all_week<T: PartialEq + Copy, TS: TimeSource>(
                 mean,
                 max,
                 stddev,
-                sparks: entry_minutes_by_day
+                sparks: week
+                    .segments
                     .into_iter()
                     .map(|ms| ms.into_iter().map(|m| spark_for(m, max, sparks)).collect())
                     .collect(),
@@ -98,9 +91,10 @@ fn calc_all_week<T: PartialEq + Copy, TS: TimeSource>(
         )
     };
     All {
-        start,
+        start: week.start,
         minutes,
         segments,
+        pauses: week.pauses,
         analysis,
     }
 }
@@ -114,25 +108,6 @@ fn spark_for<T: Copy>(m: i64, max: i64, sparks: &[T]) -> T {
     }
 }
 
-fn minutes_between(entries: &[TimeEntry], start: OffsetDateTime, stop: OffsetDateTime) -> i64 {
-    entries
-        .iter()
-        .fold(0, |sum, entry| sum + entry.minutes_between(start, stop))
-}
-
-fn minutes_between_days<TS: TimeSource>(
-    entries: &[TimeEntry],
-    start: Date,
-    stop: Date,
-    ts: &TS,
-) -> i64 {
-    minutes_between(
-        entries,
-        start.midnight().assume_offset(ts.local_offset()),
-        stop.next_day().midnight().assume_offset(ts.local_offset()),
-    )
-}
-
 fn sqrtint(n: i64) -> i64 {
     let mut i = 1;
     while i * i <= n {
@@ -143,6 +118,7 @@ fn sqrtint(n: i64) -> i64 {
 
 #[cfg(test)]
 mod tests {
+    use crate::calendar::Calendar;
     use crate::parser::parse_time_entries;
     use crate::timesource::real_time::DefaultTimeSource;
     use time::date;
@@ -164,7 +140,7 @@ mod tests {
                      2013-09-05 11:39,2013-09-05 11:49\n";
         let entries = parse_time_entries(input.as_bytes(), &DefaultTimeSource)?;
         let sparks = vec![0, 1, 2, 3, 4, 5, 6];
-        let result = super::calc(entries, &sparks, &DefaultTimeSource);
+        let result = super::calc(entries, &sparks, &Calendar::default(), &DefaultTimeSource);
         assert_eq!(
             result,
             vec![
@@ -172,6 +148,7 @@ mod tests {
                     start: date!(2013 - 07 - 28),
                     minutes: 59,
                     segments: 2,
+                    pauses: 0,
                     analysis: Some(super::AllAnalysis {
                         min: 29,
                         mean: 29,
@@ -184,18 +161,21 @@ mod tests {
                     start: date!(2013 - 08 - 04),
                     minutes: 0,
                     segments: 0,
+                    pauses: 0,
                     analysis: None
                 },
                 super::All {
                     start: date!(2013 - 08 - 11),
                     minutes: 61,
                     segments: 1,
+                    pauses: 0,
                     analysis: None
                 },
                 super::All {
                     start: date!(2013 - 08 - 18),
                     minutes: 75,
                     segments: 3, // the one that spans midnight counts for each day.
+                    pauses: 0,
                     analysis: Some(super::AllAnalysis {
                         min: 2,
                         mean: 25,
@@ -208,12 +188,14 @@ mod tests {
                     start: date!(2013 - 08 - 25),
                     minutes: 63,
                     segments: 1,
+                    pauses: 0,
                     analysis: None
                 },
                 super::All {
                     start: date!(2013 - 09 - 01),
                     minutes: 64,
                     segments: 5,
+                    pauses: 0,
                     analysis: Some(super::AllAnalysis {
                         min: 10,
                         mean: 12,
@@ -227,6 +209,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_pauses() -> TestRes {
+        let input = "2013-09-04 09:00,2013-09-04 12:00,acme\n\
+                     2013-09-04 12:30,2013-09-04 14:00,acme,resume\n\
+                     2013-09-04 14:00,2013-09-04 15:00,initech\n\
+                     2013-09-05 09:00,2013-09-05 10:00\n\
+                     2013-09-05 10:15,2013-09-05 11:00,,resume\n";
+        let entries = parse_time_entries(input.as_bytes(), &DefaultTimeSource)?;
+        let sparks = vec![0, 1, 2, 3, 4, 5, 6];
+        let result = super::calc(entries, &sparks, &Calendar::default(), &DefaultTimeSource);
+        assert_eq!(
+            result,
+            vec![super::All {
+                start: date!(2013 - 09 - 01),
+                minutes: 435,
+                segments: 3,
+                pauses: 2,
+                analysis: Some(super::AllAnalysis {
+                    min: 60,
+                    mean: 145,
+                    max: 270,
+                    stddev: 110,
+                    sparks: vec![vec![6, 1], vec![2]]
+                })
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_paused_segment() -> TestRes {
+        let input = "2013-09-04 09:00,2013-09-04 12:00\n\
+                     2013-09-04 12:30,2013-09-04 14:00,,resume\n";
+        let entries = parse_time_entries(input.as_bytes(), &DefaultTimeSource)?;
+        let result = super::calc(entries, &[0, 1], &Calendar::default(), &DefaultTimeSource);
+        assert_eq!(1, result[0].segments);
+        assert_eq!(270, result[0].minutes);
+        assert_eq!(0, result[0].analysis.as_ref().unwrap().stddev);
+        Ok(())
+    }
+
     #[test]
     fn test_spark_for() {
         let sparks = ['a', 'b', 'c', 'd', 'e', 'f', 'g'];
@@ -0,0 +1,152 @@
+use crate::calendar::Calendar;
+use crate::entry::{Time, TimeEntry};
+use crate::timesource::TimeSource;
+
+/// Session is a run of time entries on one workday that were only interrupted by pauses.
+#[derive(Debug, PartialEq)]
+pub struct Session {
+    pub entries: Vec<TimeEntry>,
+}
+
+/// Break is a pause between two entries of a session.
+#[derive(Debug, PartialEq)]
+pub struct Break {
+    pub start: Time,
+    pub stop: Time,
+}
+
+impl Session {
+    pub fn start(&self) -> &Time {
+        &self.entries[0].start
+    }
+
+    pub fn stop(&self) -> Option<&Time> {
+        self.entries.last().and_then(|entry| entry.stop.as_ref())
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.entries[0].label.as_deref()
+    }
+
+    // Minutes worked during the session, not counting breaks.
+    pub fn minutes<TS: TimeSource>(&self, ts: &TS) -> i64 {
+        self.entries.iter().map(|entry| entry.minutes(ts)).sum()
+    }
+
+    pub fn breaks(&self) -> Vec<Break> {
+        self.entries
+            .windows(2)
+            .filter_map(|pair| {
+                Some(Break {
+                    start: pair[0].stop.clone()?,
+                    stop: pair[1].start.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Break {
+    pub fn minutes(&self) -> i64 {
+        (self.stop.datetime() - self.start.datetime()).whole_minutes()
+    }
+}
+
+/// Group entries into sessions. A resume after the day rollover starts a new session, so that
+/// the night isn't counted as a break.
+pub fn calc(entries: Vec<TimeEntry>, cal: &Calendar) -> Vec<Session> {
+    let mut sessions: Vec<Session> = vec![];
+    for entry in entries {
+        match sessions.last_mut() {
+            Some(session)
+                if entry.resumed
+                    && cal.workday(entry.start.datetime())
+                        == cal.workday(session.start().datetime()) =>
+            {
+                session.entries.push(entry)
+            }
+            _ => sessions.push(Session {
+                entries: vec![entry],
+            }),
+        }
+    }
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::calendar::Calendar;
+    use crate::parser::parse_time_entries;
+    use crate::timesource::mock_time::mock_time;
+    use time::{date, offset, time};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_sessions() -> TestRes {
+        let input = "2013-09-04 09:00 -0400,2013-09-04 12:00 -0400,acme\n\
+                     2013-09-04 12:30 -0400,2013-09-04 14:00 -0400,acme,resume\n\
+                     2013-09-04 14:05 -0400,2013-09-04 15:00 -0400,acme,resume\n\
+                     2013-09-04 15:00 -0400,2013-09-04 16:00 -0400,initech\n\
+                     2013-09-05 09:00 -0400,,,resume\n";
+        let ts = mock_time(date!(2013 - 09 - 05), time!(10:00), offset!(-04:00));
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let sessions = super::calc(entries, &Calendar::default());
+        assert_eq!(3, sessions.len());
+
+        let acme = &sessions[0];
+        assert_eq!(Some("acme"), acme.label());
+        assert_eq!("2013-09-04 09:00 -0400", acme.start().to_string());
+        assert_eq!(
+            Some("2013-09-04 15:00 -0400".to_string()),
+            acme.stop().map(|t| t.to_string())
+        );
+        assert_eq!(325, acme.minutes(&ts));
+        assert_eq!(
+            vec![30, 5],
+            acme.breaks()
+                .iter()
+                .map(|b| b.minutes())
+                .collect::<Vec<_>>()
+        );
+
+        let initech = &sessions[1];
+        assert_eq!(1, initech.entries.len());
+        assert_eq!(60, initech.minutes(&ts));
+        assert!(initech.breaks().is_empty());
+
+        // Resuming the next morning starts over instead of taking a break overnight.
+        let next_day = &sessions[2];
+        assert_eq!(None, next_day.label());
+        assert_eq!(None, next_day.stop());
+        assert_eq!(60, next_day.minutes(&ts));
+        assert!(next_day.breaks().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sessions_after_midnight() -> TestRes {
+        let input = "2013-09-04 23:00 -0400,2013-09-05 00:30 -0400,acme
+                     2013-09-05 01:00 -0400,2013-09-05 02:00 -0400,acme,resume
+                     2013-09-05 05:00 -0400,2013-09-05 06:00 -0400,acme,resume
+";
+        let ts = mock_time(date!(2013 - 09 - 05), time!(10:00), offset!(-04:00));
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let cal = Calendar {
+            day_rollover: time!(04:00),
+            ..Calendar::default()
+        };
+        let sessions = super::calc(entries, &cal);
+        assert_eq!(2, sessions.len());
+        assert_eq!(
+            vec![30],
+            sessions[0]
+                .breaks()
+                .iter()
+                .map(|b| b.minutes())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(60, sessions[1].minutes(&ts));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
+use crate::calendar::Calendar;
 use crate::entry::TimeEntry;
-use crate::iter::{each_day_in_week, each_week};
 use crate::timesource::TimeSource;
+use crate::totals::{each_week_totals, WeekTotals};
 use std::fmt::{self, Display, Formatter};
 use time::{Date, Duration};
 
@@ -57,21 +58,30 @@ impl Options {
     }
 }
 
-pub fn prepare<TS: TimeSource>(entries: Vec<TimeEntry>, ts: &TS, opts: Options) -> Report {
+pub fn prepare<TS: TimeSource>(
+    entries: Vec<TimeEntry>,
+    cal: &Calendar,
+    ts: &TS,
+    opts: Options,
+) -> Report {
+    prepare_from_totals(each_week_totals(entries, cal, ts), opts)
+}
+
+/// Like prepare, for weeks that have already been added up, e.g. by the cache.
+pub fn prepare_from_totals(weeks: impl IntoIterator<Item = WeekTotals>, opts: Options) -> Report {
     let mut state = None;
-    for (week_start, entries) in each_week(entries, ts) {
-        state = Some(prepare_week(state, week_start, entries, ts));
+    for week in weeks {
+        state = Some(prepare_week(state, week));
     }
     finish(state, opts)
 }
 
-fn prepare_week<TS: TimeSource>(
-    state: Option<State>,
-    week_start: Date,
-    entries: Vec<TimeEntry>,
-    ts: &TS,
-) -> State {
-    let week = convert_week(week_start, entries, ts);
+fn prepare_week(state: Option<State>, totals: WeekTotals) -> State {
+    let week_start = totals.start;
+    let week = Week {
+        start: totals.start,
+        minutes: totals.days,
+    };
     match state {
         None => {
             let month = Month {
@@ -132,23 +142,6 @@ fn prepare_week<TS: TimeSource>(
     }
 }
 
-fn convert_week<TS: TimeSource>(start: Date, entries: Vec<TimeEntry>, ts: &TS) -> Week {
-    let mut minutes = [0; 7];
-    for (day_start, entries) in each_day_in_week(entries, start, ts) {
-        let i = (day_start - start).whole_days();
-        minutes[i as usize] = minutes_on_day(day_start, entries, ts);
-    }
-    Week { start, minutes }
-}
-
-fn minutes_on_day<TS: TimeSource>(start: Date, entries: Vec<TimeEntry>, ts: &TS) -> i64 {
-    let stop = start.next_day().midnight().assume_offset(ts.local_offset());
-    let start = start.midnight().assume_offset(ts.local_offset());
-    entries
-        .iter()
-        .fold(0, |sum, entry| sum + entry.minutes_between(start, stop))
-}
-
 fn finish(state: Option<State>, opts: Options) -> Report {
     let years = match state {
         Some(State {
@@ -166,12 +159,15 @@ fn finish(state: Option<State>, opts: Options) -> Report {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::{prepare, Month, Options, Report, Week, Year};
+    use crate::calendar::Calendar;
     use crate::entry::TimeEntry;
     use crate::parser::parse_time_entries;
-    use crate::timesource::mock_time::mock_time;
+    use crate::timesource::mock_time::{mock_time, mock_time_in};
     use crate::timesource::real_time::DefaultTimeSource;
+    use crate::zone::Zone;
     use pretty_assertions::assert_eq;
     use time::{date, offset, time};
 
@@ -183,6 +179,7 @@ mod tests {
         assert_eq!(
             prepare(
                 entries,
+                &Calendar::default(),
                 &DefaultTimeSource,
                 Options {
                     include_totals: true,
@@ -206,6 +203,7 @@ mod tests {
         assert_eq!(
             prepare(
                 entries,
+                &Calendar::default(),
                 &DefaultTimeSource,
                 Options {
                     include_totals: true,
@@ -232,6 +230,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_days_from_another_season() -> TestRes {
+        // In the summer, a day in the winter is still midnight to midnight on the clock.
+        let zone = Zone::named("EST5EDT,M3.2.0,M11.1.0")?;
+        let ts = mock_time_in(date!(2014 - 07 - 01), time!(12:00), zone);
+        let input = "2013-11-16 00:00,2013-11-17 00:00\n";
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let opts = Options {
+            include_totals: false,
+            only_show_per_year: false,
+        };
+        assert_eq!(
+            prepare(entries, &Calendar::default(), &ts, opts),
+            Report {
+                opts,
+                years: vec![Year {
+                    year: 2013,
+                    months: vec![Month {
+                        month: 11,
+                        weeks: vec![Week {
+                            start: date!(2013 - 11 - 10),
+                            minutes: [0, 0, 0, 0, 0, 0, 1440],
+                        }]
+                    }]
+                }]
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_day_rollover() -> TestRes {
+        let ts = mock_time(date!(2013 - 09 - 10), time!(12:00), offset!(-04:00));
+        let input = "2013-09-03 22:00 -0400,2013-09-04 02:00 -0400\n\
+                     2013-09-04 03:30 -0400,2013-09-04 04:30 -0400\n";
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let cal = Calendar {
+            day_rollover: time!(04:00),
+            ..Calendar::default()
+        };
+        let opts = Options {
+            include_totals: false,
+            only_show_per_year: false,
+        };
+        assert_eq!(
+            prepare(entries, &cal, &ts, opts),
+            Report {
+                opts,
+                years: vec![Year {
+                    year: 2013,
+                    months: vec![Month {
+                        month: 9,
+                        weeks: vec![Week {
+                            start: date!(2013 - 09 - 01),
+                            minutes: [0, 0, 270, 30, 0, 0, 0],
+                        }]
+                    }]
+                }]
+            }
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_entry_covering_now() -> TestRes {
         let ts = mock_time(date!(2013 - 09 - 04), time!(12:00), offset!(-04:00));
@@ -240,6 +301,7 @@ mod tests {
         assert_eq!(
             prepare(
                 entries,
+                &Calendar::default(),
                 &ts,
                 Options {
                     include_totals: true,
@@ -273,6 +335,7 @@ mod tests {
         assert_eq!(
             prepare(
                 entries,
+                &Calendar::default(),
                 &DefaultTimeSource,
                 Options {
                     include_totals: false,
@@ -322,6 +385,7 @@ mod tests {
         assert_eq!(
             prepare(
                 entries,
+                &Calendar::default(),
                 &DefaultTimeSource,
                 Options {
                     include_totals: true,
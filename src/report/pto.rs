@@ -3,15 +3,22 @@ use std::fmt::{self, Display};
 
 use time::{Date, Duration};
 
+use crate::calendar::Calendar;
 use crate::entry::TimeEntry;
 use crate::iter::each_week;
 use crate::timesource::TimeSource;
 
-pub fn prepare<TS: TimeSource>(entries: Vec<TimeEntry>, full_week: i64, ts: &TS) -> Report {
+pub fn prepare<TS: TimeSource>(
+    entries: Vec<TimeEntry>,
+    full_week: i64,
+    cal: &Calendar,
+    ts: &TS,
+) -> Report {
+    let zone = ts.zone();
     let mut weeks = BTreeMap::new();
-    for (week_start, entries) in each_week(entries, ts) {
-        let start = week_start.midnight().assume_offset(ts.local_offset());
-        let stop = start + Duration::week();
+    for (week_start, entries) in each_week(entries, cal, ts) {
+        let start = cal.start_of_day(week_start, &zone);
+        let stop = cal.start_of_day(week_start + Duration::week(), &zone);
         let minutes = entries.iter().map(|e| e.minutes_between(start, stop)).sum();
         weeks.insert(week_start, minutes);
     }
@@ -47,3 +54,30 @@ impl Display for Report {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::prepare;
+    use crate::calendar::Calendar;
+    use crate::parser::parse_time_entries;
+    use crate::timesource::mock_time::mock_time_in;
+    use crate::zone::Zone;
+    use pretty_assertions::assert_eq;
+    use time::{date, time};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_week_from_another_season() -> TestRes {
+        let zone = Zone::named("EST5EDT,M3.2.0,M11.1.0")?;
+        let ts = mock_time_in(date!(2014 - 07 - 01), time!(12:00), zone);
+        let input = "2013-11-16 00:00,2013-11-17 00:00\n";
+        let entries = parse_time_entries(input.as_bytes(), &ts)?;
+        let report = prepare(entries, 2400, &Calendar::default(), &ts);
+        assert_eq!(
+            vec![(date!(2013 - 11 - 10), 1440)],
+            report.weeks.into_iter().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use notify::event::ModifyKind;
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use reqwest::{Client, Url};
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use t::file;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{self, stdout, AsyncWriteExt};
@@ -39,19 +39,24 @@ async fn sync_main(url: String, logger: &mut Logger) -> Result<(), Box<dyn Error
         client,
     };
 
-    logger.debug(|| format!("set up watcher for {t:?}")).await;
+    // t replaces the data file with a rename when it changes it, so watch the directory that it's
+    // in rather than the file itself.
+    let dir = match t.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    logger
+        .debug(|| format!("set up watcher for {t:?} in {dir:?}"))
+        .await;
     let (mut watcher, mut rx) = async_watcher()?;
-    watcher.watch(&t, RecursiveMode::Recursive)?; // todo not recursive
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
 
     syncer.sync(logger).await;
 
     while let Some(res) = rx.recv().await {
         match res {
             Err(e) => return Err(e.into()),
-            Ok(Event {
-                kind: EventKind::Modify(ModifyKind::Data(_)),
-                ..
-            }) => syncer.sync(logger).await,
+            Ok(event) if is_change_to(&event, &t) => syncer.sync(logger).await,
             Ok(event) => logger.debug(|| format!("ignore {event:?}")).await,
         };
     }
@@ -59,6 +64,18 @@ async fn sync_main(url: String, logger: &mut Logger) -> Result<(), Box<dyn Error
     Ok(())
 }
 
+fn is_change_to(event: &Event, t: &Path) -> bool {
+    let changed = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_))
+    );
+    changed
+        && event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == t.file_name())
+}
+
 struct Syncer {
     url: Url,
     t: PathBuf,
@@ -74,7 +91,13 @@ impl Syncer {
 
     async fn try_sync(&self, logger: &mut Logger) -> Result<(), Box<dyn Error>> {
         let url = self.url.join("api/t-data-file")?;
-        let t_data = tokio::fs::read(&self.t).await?;
+        // lock_shared sleeps while another t has the file, which mustn't hold up the runtime.
+        let path = self.t.clone();
+        let t_data = tokio::task::spawn_blocking(move || {
+            let _lock = t::lock::lock_shared(&path)?;
+            std::fs::read(&path)
+        })
+        .await??;
         logger
             .debug(|| format!("upload {:?} to {url}", self.t))
             .await;
@@ -1,4 +1,7 @@
+use crate::zone::Zone;
+
 pub mod real_time {
+    use crate::zone::{self, Zone};
     use time::{OffsetDateTime, UtcOffset};
 
     #[derive(Clone)]
@@ -6,22 +9,30 @@ pub mod real_time {
 
     impl super::TimeSource for DefaultTimeSource {
         fn now(&self) -> OffsetDateTime {
-            OffsetDateTime::now_local()
+            let now = OffsetDateTime::now_utc();
+            now.to_offset(zone::local().offset_of(now))
         }
 
         fn local_offset(&self) -> UtcOffset {
-            UtcOffset::current_local_offset()
+            zone::local().offset_of(OffsetDateTime::now_utc())
+        }
+
+        fn zone(&self) -> Zone {
+            zone::local().clone()
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 pub mod mock_time {
+    use crate::zone::Zone;
     use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
 
     #[derive(Clone)]
     pub struct MockTimeSource {
         dt: OffsetDateTime,
+        zone: Zone,
     }
 
     impl super::TimeSource for MockTimeSource {
@@ -32,11 +43,20 @@ pub mod mock_time {
         fn local_offset(&self) -> UtcOffset {
             self.dt.offset()
         }
+
+        fn zone(&self) -> Zone {
+            self.zone.clone()
+        }
     }
 
     pub fn mock_time(date: Date, time: Time, offset: UtcOffset) -> MockTimeSource {
+        mock_time_in(date, time, offset.into())
+    }
+
+    pub fn mock_time_in(date: Date, time: Time, zone: Zone) -> MockTimeSource {
         MockTimeSource {
-            dt: PrimitiveDateTime::new(date, time).assume_offset(offset),
+            dt: zone.assume(PrimitiveDateTime::new(date, time)),
+            zone,
         }
     }
 }
@@ -44,4 +64,9 @@ pub mod mock_time {
 pub trait TimeSource {
     fn local_offset(&self) -> time::UtcOffset;
     fn now(&self) -> time::OffsetDateTime;
+
+    /// The time zone that times without a UTC offset are in.
+    fn zone(&self) -> Zone {
+        self.local_offset().into()
+    }
 }
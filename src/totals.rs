@@ -0,0 +1,159 @@
+use crate::calendar::Calendar;
+use crate::entry::TimeEntry;
+use crate::iter::{each_day_in_week, each_week};
+use crate::timesource::TimeSource;
+use time::{Date, Duration};
+
+/// WeekTotals is what the weekly reports need to know about one week of entries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeekTotals {
+    pub start: Date,
+    /// How many entries overlap the week.
+    pub entries: usize,
+    /// How many of those entries resumed the one before them.
+    pub pauses: usize,
+    /// Minutes worked during the week.
+    pub minutes: i64,
+    /// Minutes worked on each day of the week.
+    pub days: [i64; 7],
+    /// The minutes in each segment of work, for each day that has entries. An entry that resumed
+    /// the one before it adds to that entry's segment.
+    pub segments: Vec<Vec<i64>>,
+}
+
+impl WeekTotals {
+    /// A week without any entries.
+    pub fn empty(start: Date) -> Self {
+        Self {
+            start,
+            entries: 0,
+            pauses: 0,
+            minutes: 0,
+            days: [0; 7],
+            segments: vec![],
+        }
+    }
+}
+
+const ONE_WEEK: Duration = Duration::days(7);
+
+/// The totals for every week from the first entry to the last, including the empty weeks in
+/// between.
+pub fn each_week_totals<'a, I: IntoIterator<Item = TimeEntry> + 'a, TS: TimeSource>(
+    entries: I,
+    cal: &'a Calendar,
+    ts: &'a TS,
+) -> impl Iterator<Item = WeekTotals> + 'a {
+    each_week(entries, cal, ts).map(move |(start, entries)| week_totals(start, entries, cal, ts))
+}
+
+fn week_totals<TS: TimeSource>(
+    start: Date,
+    entries: Vec<TimeEntry>,
+    cal: &Calendar,
+    ts: &TS,
+) -> WeekTotals {
+    let zone = ts.zone();
+    let week_start = cal.start_of_day(start, &zone);
+    let week_end = cal.start_of_day(start + ONE_WEEK, &zone);
+    let mut totals = WeekTotals {
+        entries: entries.len(),
+        pauses: entries.iter().filter(|entry| entry.resumed).count(),
+        minutes: entries.iter().fold(0, |sum, entry| {
+            sum + entry.minutes_between(week_start, week_end)
+        }),
+        ..WeekTotals::empty(start)
+    };
+    for (day, entries) in each_day_in_week(entries, start, cal, ts) {
+        let day_start = cal.start_of_day(day, &zone);
+        let day_end = cal.start_of_day(day.next_day(), &zone);
+        let mut segments: Vec<i64> = vec![];
+        for entry in &entries {
+            let minutes = entry.minutes_between(day_start, day_end);
+            match segments.last_mut() {
+                Some(segment) if entry.resumed => *segment += minutes,
+                _ => segments.push(minutes),
+            }
+        }
+        totals.days[(day - start).whole_days() as usize] = segments.iter().sum();
+        if !entries.is_empty() {
+            totals.segments.push(segments);
+        }
+    }
+    totals
+}
+
+/// Add more weeks after the ones that are already there, with empty weeks in any gap between
+/// them.
+pub fn append_weeks(weeks: &mut Vec<WeekTotals>, more: Vec<WeekTotals>) {
+    if let (Some(last), Some(next)) = (weeks.last(), more.first()) {
+        let mut start = last.start + ONE_WEEK;
+        while start < next.start {
+            weeks.push(WeekTotals::empty(start));
+            start += ONE_WEEK;
+        }
+    }
+    weeks.extend(more);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_weeks, each_week_totals, WeekTotals};
+    use crate::calendar::Calendar;
+    use crate::parser::parse_time_entries;
+    use crate::timesource::real_time::DefaultTimeSource;
+    use pretty_assertions::assert_eq;
+    use time::date;
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn test_each_week_totals() -> TestRes {
+        let input = "2013-09-04 09:00,2013-09-04 12:00,acme\n\
+                     2013-09-04 12:30,2013-09-04 14:00,acme,resume\n\
+                     2013-09-05 23:00,2013-09-06 01:00\n\
+                     2013-09-20 10:00,2013-09-20 10:30\n";
+        let entries = parse_time_entries(input.as_bytes(), &DefaultTimeSource)?;
+        let cal = Calendar::default();
+        let weeks: Vec<WeekTotals> = each_week_totals(entries, &cal, &DefaultTimeSource).collect();
+        assert_eq!(
+            vec![
+                WeekTotals {
+                    start: date!(2013 - 09 - 01),
+                    entries: 3,
+                    pauses: 1,
+                    minutes: 390,
+                    days: [0, 0, 0, 270, 60, 60, 0],
+                    segments: vec![vec![270], vec![60], vec![60]],
+                },
+                WeekTotals::empty(date!(2013 - 09 - 08)),
+                WeekTotals {
+                    start: date!(2013 - 09 - 15),
+                    entries: 1,
+                    pauses: 0,
+                    minutes: 30,
+                    days: [0, 0, 0, 0, 0, 30, 0],
+                    segments: vec![vec![30]],
+                },
+            ],
+            weeks
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_weeks() {
+        let mut weeks = vec![WeekTotals::empty(date!(2013 - 09 - 01))];
+        append_weeks(&mut weeks, vec![WeekTotals::empty(date!(2013 - 09 - 22))]);
+        let starts: Vec<_> = weeks.iter().map(|week| week.start).collect();
+        assert_eq!(
+            vec![
+                date!(2013 - 09 - 01),
+                date!(2013 - 09 - 08),
+                date!(2013 - 09 - 15),
+                date!(2013 - 09 - 22)
+            ],
+            starts
+        );
+    }
+}
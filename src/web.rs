@@ -4,12 +4,14 @@ use rocket::data::ToByteUnit;
 use rocket::fs::FileServer;
 use rocket::serde::{json::Json, Serialize};
 use rocket::{get, put, routes, Data, State};
+use t::calendar::Calendar;
 use t::{extents, query};
 
 pub struct Options {
     pub static_root: PathBuf,
     pub t_data_file: PathBuf,
     pub time_source: TimeSource,
+    pub calendar: Calendar,
 }
 
 pub fn main(opts: Options) {
@@ -21,7 +23,7 @@ async fn web_main(opts: Options) {
     rocket::build()
         .manage(opts)
         .mount("/", FileServer::from(&static_root))
-        .mount("/", routes![status, upload])
+        .mount("/", routes![status, entries, upload])
         .launch()
         .await
         .unwrap();
@@ -48,14 +50,16 @@ struct WeekStatus {
 #[get("/api/status")]
 fn status(opts: &State<Options>) -> Result<Json<Status>, String> {
     let ctx = query::for_web(opts.t_data_file.clone(), &opts.time_source);
-    let entries = ctx.all().map_err(|e| format!("error: {e}"))?;
+    let entries = ctx
+        .recent(4, &opts.calendar)
+        .map_err(|e| format!("error: {e}"))?;
     Ok(Status {
         working: entries.is_working(),
         last_update: entries.last_update(),
-        minutes_today: entries.minutes_between(extents::today()),
-        minutes_this_week: entries.minutes_between(extents::this_week()),
+        minutes_today: entries.minutes_between(extents::today(&opts.calendar)),
+        minutes_this_week: entries.minutes_between(extents::this_week(&opts.calendar)),
         recent: entries
-            .recent_weeks(4)
+            .recent_weeks(4, &opts.calendar)
             .into_iter()
             .map(|w| WeekStatus {
                 start_of_week: w.start.format("%Y-%m-%d"),
@@ -67,12 +71,53 @@ fn status(opts: &State<Options>) -> Result<Json<Status>, String> {
     .into())
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct EntryStatus {
+    start: String,
+    stop: Option<String>,
+    label: Option<String>,
+    notes: Vec<String>,
+    resumed: bool,
+}
+
+#[get("/api/entries")]
+fn entries(opts: &State<Options>) -> Result<Json<Vec<EntryStatus>>, String> {
+    let ctx = query::for_web(opts.t_data_file.clone(), &opts.time_source);
+    let entries = ctx
+        .this_week(&opts.calendar)
+        .map_err(|e| format!("error: {e}"))?;
+    Ok(entries
+        .between(extents::today(&opts.calendar))
+        .into_iter()
+        .map(|e| EntryStatus {
+            start: format!("{}", e.start),
+            stop: e.stop.map(|t| format!("{}", t)),
+            label: e.label,
+            notes: e.notes,
+            resumed: e.resumed,
+        })
+        .collect::<Vec<_>>()
+        .into())
+}
+
 #[put("/api/t-data-file", data = "<body>")]
 async fn upload(opts: &State<Options>, body: Data<'_>) -> std::io::Result<()> {
-    body.open(128.mebibytes())
-        .into_file(&opts.t_data_file)
-        .await?;
-    Ok(())
+    let contents = body.open(128.mebibytes()).into_bytes().await?;
+    if !contents.is_complete() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "t.csv is too big to upload",
+        ));
+    }
+    // Waiting for the lock blocks, so it's done on a thread that's allowed to block.
+    let path = opts.t_data_file.clone();
+    rocket::tokio::task::spawn_blocking(move || {
+        t::file::replace_data_file(path, &contents)
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    })
+    .await
+    .map_err(std::io::Error::other)?
 }
 
 trait TS: t::timesource::TimeSource + Send + Sync {}
@@ -97,6 +142,10 @@ impl t::timesource::TimeSource for TimeSource {
     fn now(&self) -> time::OffsetDateTime {
         self.ts.now()
     }
+
+    fn zone(&self) -> t::zone::Zone {
+        self.ts.zone()
+    }
 }
 
 impl t::timesource::TimeSource for &TimeSource {
@@ -107,4 +156,8 @@ impl t::timesource::TimeSource for &TimeSource {
     fn now(&self) -> time::OffsetDateTime {
         self.ts.now()
     }
+
+    fn zone(&self) -> t::zone::Zone {
+        self.ts.zone()
+    }
 }
@@ -0,0 +1,149 @@
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::{Arc, OnceLock};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+/// Zone is the time zone that times written without a UTC offset are in. A named zone knows when
+/// its offset changes, so a time from last winter gets last winter's offset, even in the summer.
+#[derive(Clone)]
+pub enum Zone {
+    Fixed(UtcOffset),
+    Named(String, Arc<tz::TimeZone>),
+}
+
+static LOCAL: OnceLock<Zone> = OnceLock::new();
+
+impl Zone {
+    /// Look up a zone by its IANA name, like America/New_York, in the system's zoneinfo. A POSIX
+    /// TZ string, like EST5EDT,M3.2.0,M11.1.0, works too.
+    pub fn named(name: &str) -> Result<Self, String> {
+        tz::TimeZone::from_posix_tz(name)
+            .map(|tz| Self::Named(name.to_string(), Arc::new(tz)))
+            .map_err(|_| format!("unknown time zone {name:?}"))
+    }
+
+    /// The UTC offset at an instant.
+    pub fn offset_of(&self, instant: OffsetDateTime) -> UtcOffset {
+        match self {
+            Self::Fixed(offset) => *offset,
+            Self::Named(_, tz) => tz
+                .find_local_time_type(instant.timestamp())
+                .map_or(UtcOffset::UTC, |t| UtcOffset::seconds(t.ut_offset())),
+        }
+    }
+
+    /// The UTC offset of a time on the clock. When the clocks go back, a time that happens twice
+    /// is taken to be the first one. When they go forward, a time that's skipped gets the offset
+    /// from before the change.
+    pub fn offset_at(&self, local: PrimitiveDateTime) -> UtcOffset {
+        if let Self::Fixed(offset) = self {
+            return *offset;
+        }
+        // No zone changes its offset more than once in a couple of days.
+        let utc = local.assume_utc();
+        let before = self.offset_of(utc - Duration::day());
+        let after = self.offset_of(utc + Duration::day());
+        [before, after]
+            .iter()
+            .copied()
+            .find(|&offset| self.offset_of(local.assume_offset(offset)) == offset)
+            .unwrap_or(before)
+    }
+
+    /// The instant that a time on the clock refers to.
+    pub fn assume(&self, local: PrimitiveDateTime) -> OffsetDateTime {
+        local.assume_offset(self.offset_at(local))
+    }
+}
+
+impl Display for Zone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(offset) => write!(f, "{}", offset.format("%z")),
+            Self::Named(name, _) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl Debug for Zone {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Zone{{{self}}}")
+    }
+}
+
+impl From<UtcOffset> for Zone {
+    fn from(offset: UtcOffset) -> Self {
+        Self::Fixed(offset)
+    }
+}
+
+/// Set up the local zone. $TZ takes precedence over `configured`, which is time_zone from the
+/// config, and /etc/localtime is used if neither is set.
+pub fn set_up_local(configured: Option<&str>) -> Result<(), String> {
+    let zone = match (env_zone(), configured) {
+        (Some(zone), _) => zone,
+        (None, Some(name)) => Zone::named(name).map_err(|e| format!("time_zone: {e}"))?,
+        (None, None) => system_zone(),
+    };
+    // If something already needed the local zone, it was the same one, just without the config.
+    let _ = LOCAL.set(zone);
+    Ok(())
+}
+
+/// The local zone, from $TZ or /etc/localtime, unless set_up_local picked a different one.
+pub fn local() -> &'static Zone {
+    LOCAL.get_or_init(|| env_zone().unwrap_or_else(system_zone))
+}
+
+fn env_zone() -> Option<Zone> {
+    std::env::var("TZ")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .and_then(|name| Zone::named(&name).ok())
+}
+
+// Without zoneinfo, the best there is is the offset right now.
+fn system_zone() -> Zone {
+    match tz::TimeZone::local() {
+        Ok(tz) => Zone::Named("localtime".to_string(), Arc::new(tz)),
+        Err(_) => Zone::Fixed(UtcOffset::current_local_offset()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Zone;
+    use pretty_assertions::assert_eq;
+    use time::{date, offset, time};
+
+    type TestRes = Result<(), Box<dyn std::error::Error>>;
+
+    // A rule instead of a name, so that the tests don't need the system's zoneinfo.
+    const NEW_YORK: &str = "EST5EDT,M3.2.0,M11.1.0";
+
+    #[test]
+    fn test_offset_at() -> TestRes {
+        let zone = Zone::named(NEW_YORK)?;
+        let at = |d, t| zone.offset_at(time::PrimitiveDateTime::new(d, t));
+        assert_eq!(offset!(-05:00), at(date!(2023 - 01 - 02), time!(09:00)));
+        assert_eq!(offset!(-04:00), at(date!(2023 - 07 - 03), time!(09:00)));
+        // The clocks go from 2:00 to 3:00, and back from 2:00 to 1:00.
+        assert_eq!(offset!(-05:00), at(date!(2023 - 03 - 12), time!(02:30)));
+        assert_eq!(offset!(-04:00), at(date!(2023 - 03 - 12), time!(03:00)));
+        assert_eq!(offset!(-04:00), at(date!(2023 - 11 - 05), time!(01:30)));
+        assert_eq!(offset!(-05:00), at(date!(2023 - 11 - 05), time!(02:00)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed() {
+        let zone: Zone = offset!(+01:00).into();
+        let at = date!(2023 - 07 - 03).with_time(time!(09:00));
+        assert_eq!(offset!(+01:00), zone.offset_at(at));
+        assert_eq!("+0100", zone.to_string());
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert!(Zone::named("Nowhere/Special").is_err());
+    }
+}
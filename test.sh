@@ -94,6 +94,23 @@ fixt 2013-09.csv \
 fixt 2013-11.csv \
   assert_diff 2013-11.pto -- pto
 
+if [ "$MODE" != ruby ]; then
+  fixt 2013-09.csv \
+    assert_diff 2013-09-monday.all -- --week-start monday all
+
+  fixt 2013-09.csv \
+    assert_diff 2013-09-monday.days -- --week-start monday days $days_args
+
+  fixt 2013-11.csv \
+    assert_diff 2013-11-monday.days -- --week-start monday days $days_args
+
+  fixt 2013-09.csv \
+    assert_diff 2013-09-monday.pto -- --week-start monday pto
+
+  fixt 2013-11.csv \
+    assert_diff 2013-11-monday.pto -- --week-start monday pto
+fi
+
 echo T edit
 cat <<EOF > "${TMPDIR}/editor.rb"
 File.open(ARGV[0], "w") do |f|